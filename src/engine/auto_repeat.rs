@@ -0,0 +1,117 @@
+use super::BinaryAxis;
+
+// Turns a held button into a stream of repeated actions: one action as soon as the button is pressed, then once the button has been held for `delay` seconds, one more action every `rate` seconds.
+// A rate of 0 means "instant": once the delay has passed, the action repeats as many times as the caller will allow, every frame.
+pub struct AutoRepeat {
+    delay: f32,
+    rate: f32,
+}
+
+impl AutoRepeat {
+    pub fn new(delay: f32, rate: f32) -> Self {
+        Self { delay, rate }
+    }
+
+    pub fn from_millis(delay_millis: u32, rate_millis: u32) -> Self {
+        Self::new(delay_millis as f32 / 1000.0, rate_millis as f32 / 1000.0)
+    }
+
+    // Returns how many times the action should happen this frame. In instant mode, this returns u32::max_value(), so callers should stop repeating as soon as the action fails.
+    pub fn triggers_this_frame(&self, axis: &BinaryAxis) -> u32 {
+        if !axis.pressed() {
+            return 0;
+        }
+
+        let held_time = axis.time_in_state();
+        if held_time >= self.delay && self.rate <= 0.0 {
+            return u32::max_value();
+        }
+
+        let previous_triggers = if axis.pressed_this_frame() {
+            0
+        }
+        else {
+            self.triggers_after(held_time - axis.frame_time())
+        };
+
+        self.triggers_after(held_time).saturating_sub(previous_triggers)
+    }
+
+    // the total number of times the action has happened after the button has been held for this long
+    fn triggers_after(&self, held_time: f32) -> u32 {
+        if held_time < self.delay {
+            1
+        }
+        else if self.rate <= 0.0 {
+            u32::max_value()
+        }
+        else {
+            2 + ((held_time - self.delay) / self.rate) as u32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AutoRepeat;
+    use super::super::BinaryAxis;
+
+    // powers of two, so the held time adds up exactly
+    const FRAME: f32 = 1.0 / 64.0;
+    const DELAY: f32 = 0.125;
+    const RATE: f32 = 0.0625;
+
+    // Holds the button for the given number of frames, and returns how many times the action triggered on each one
+    fn triggers_while_held(auto_repeat: &AutoRepeat, frames: usize) -> Vec<u32> {
+        let mut axis = BinaryAxis::new();
+        axis.set_held(true);
+
+        (0..frames).map(|_| {
+            axis.update(FRAME);
+            auto_repeat.triggers_this_frame(&axis)
+        }).collect()
+    }
+
+    #[test]
+    fn pressing_triggers_once_then_waits_for_the_delay() {
+        let triggers = triggers_while_held(&AutoRepeat::new(DELAY, RATE), 7);
+        assert_eq!(triggers, vec![1, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn first_repeat_happens_once_the_delay_has_charged() {
+        let triggers = triggers_while_held(&AutoRepeat::new(DELAY, RATE), 8);
+        assert_eq!(triggers[7], 1);
+    }
+
+    #[test]
+    fn repeats_at_a_steady_rate_after_the_delay() {
+        let triggers = triggers_while_held(&AutoRepeat::new(DELAY, RATE), 20);
+
+        // one repeat every 4 frames, starting on frame 8
+        assert_eq!(&triggers[7..], &[1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn zero_rate_repeats_without_limit_after_the_delay() {
+        let triggers = triggers_while_held(&AutoRepeat::new(DELAY, 0.0), 10);
+
+        assert_eq!(&triggers[..7], &[1, 0, 0, 0, 0, 0, 0]);
+        assert!(triggers[7..].iter().all(|&count| count == u32::max_value()));
+    }
+
+    #[test]
+    fn releasing_stops_the_repeats() {
+        let auto_repeat = AutoRepeat::new(DELAY, 0.0);
+        let mut axis = BinaryAxis::new();
+        axis.set_held(true);
+        for _ in 0..10 {
+            axis.update(FRAME);
+        }
+
+        axis.set_held(false);
+        axis.update(FRAME);
+
+        assert_eq!(auto_repeat.triggers_this_frame(&axis), 0);
+    }
+}
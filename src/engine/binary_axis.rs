@@ -9,6 +9,8 @@ pub struct BinaryAxis {
 
     frames_in_previous: u32,
     time_in_previous: f32,
+
+    frame_time: f32,
 }
 
 impl BinaryAxis {
@@ -20,6 +22,8 @@ impl BinaryAxis {
 
             frames_in_previous: 0,
             time_in_previous: 0.0,
+
+            frame_time: 0.0,
         }
     }
 
@@ -29,6 +33,10 @@ impl BinaryAxis {
             &ButtonState::Release => false,
         };
 
+        self.set_held(new_state);
+    }
+
+    pub fn set_held(&mut self, new_state: bool) {
         if self.currently_held != new_state {
             self.currently_held = new_state;
 
@@ -43,6 +51,7 @@ impl BinaryAxis {
     pub fn update(&mut self, dt: f32) {
         self.frames_in_state += 1;
         self.time_in_state += dt;
+        self.frame_time = dt;
     }
 
     pub fn pressed(&self) -> bool {
@@ -51,4 +60,13 @@ impl BinaryAxis {
     pub fn pressed_this_frame(&self) -> bool {
         self.pressed() && self.frames_in_state < 2
     }
+
+    pub fn time_in_state(&self) -> f32 {
+        self.time_in_state
+    }
+
+    // the delta time of the most recent update
+    pub fn frame_time(&self) -> f32 {
+        self.frame_time
+    }
 }
\ No newline at end of file
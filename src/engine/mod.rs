@@ -2,6 +2,9 @@
 mod binary_axis;
 pub use self::binary_axis::BinaryAxis;
 
+mod auto_repeat;
+pub use self::auto_repeat::AutoRepeat;
+
 pub mod intvector;
 
 use piston::event_loop::EventSettings;
@@ -16,14 +16,31 @@ extern crate num_traits;
 
 extern crate rand;
 
+use std::process;
+
 use piston_window::WindowSettings;
 
 mod engine;
 mod tetris;
 
 fn main() {
+    let options = match tetris::LaunchOptions::from_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            eprintln!("{}", tetris::LaunchOptions::usage());
+            process::exit(1);
+        }
+    };
+
     let mut window = WindowSettings::new("Dark Tetris", [640, 480]).exit_on_esc(true).build().unwrap();
-    let mut app = tetris::TetrisApp::new(&mut window);
+    let mut app = match tetris::TetrisApp::new(&mut window, options) {
+        Ok(app) => app,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    };
 
     engine::exec(&mut app);
 }
\ No newline at end of file
@@ -2,53 +2,320 @@
 use piston::event_loop::EventSettings;
 use piston_window::{Window, PistonWindow, RenderArgs, AfterRenderArgs, UpdateArgs, IdleArgs, Input};
 
-use num_traits::{Zero, One};
+use num_traits::One;
 
 use cgmath::{Decomposed, Vector3, Matrix4, Quaternion, ortho};
-use rand::thread_rng;
+use image::{Rgb, Rgba};
+use rand::{thread_rng, Rng};
 
 use ::engine::App;
 use ::engine::intvector::IntVector2;
 
 use super::tetris_input::TetrisInput;
 use super::render::RenderState;
-use super::tetromino::Tetromino;
-use super::playfield::Playfield;
+use super::tetromino::{Tetromino, PieceType};
+use super::game::{Game, GameConfig, GameEvent, GameOverReason};
+use super::launch_options::LaunchOptions;
+use super::replay::Replay;
 
-enum TetrominoState {
-    Spawning(f32),
-    Active(Tetromino, f32),
-    GameOver,
-}
+// distance between the edge of the playfield and the piece previews/hold slot, and between each previewed piece
+const PREVIEW_MARGIN: i8 = 3;
+const PREVIEW_SPACING: i8 = 3;
+
+// alpha for normal blocks, and for the ghost piece that shows where the active piece will land
+const OPAQUE_ALPHA: u8 = 255;
+const GHOST_ALPHA: u8 = 60;
+
+// alpha for the leftover blocks behind the game over screen
+const DIMMED_ALPHA: u8 = 70;
+
+// size of one font pixel, in playfield cells, and the color of the text
+const TEXT_SCALE: f32 = 0.2;
+const TEXT_COLOR: [u8; 4] = [255, 255, 255, 255];
+
+// empty space kept above and below the playfield, in cells. The space underneath is where the replay status goes
+const FRAME_MARGIN: f32 = 2.0;
 
 pub struct TetrisApp<'app> {
     window: &'app mut PistonWindow,
     render_state: RenderState,
 
-    playfield: Playfield,
-    state: TetrominoState,
+    game_config: GameConfig,
+    game: Game,
+    replay: Replay,
+    paused: bool,
     input: TetrisInput,
 
     projection_matrix: Matrix4<f32>,
 }
 
 impl<'app> TetrisApp<'app> {
-    pub fn new(window: &'app mut PistonWindow) -> Self {
+    pub fn new(window: &'app mut PistonWindow, options: LaunchOptions) -> Result<Self, String> {
         let window_size = window.window.size();
 
+        // if we were given a replay, the game has to use the recorded seed and rules, and the replay drives the input
+        let (game_config, seed, input) = match options.replay_path {
+            Some(ref replay_path) => {
+                let replay = Replay::load(replay_path).map_err(|error| format!("failed to load replay {}: {}", replay_path.display(), error))?;
+                println!("Playing replay {}, recorded with version {}, seed {}", replay_path.display(), replay.game_version, replay.seed);
+                if replay.game_version != env!("CARGO_PKG_VERSION") {
+                    println!("Warning: this replay was recorded with a different version of the game, and may not play back correctly");
+                }
+
+                (replay.config, replay.seed, TetrisInput::from_replay(replay, options.replay_start_frame))
+            }
+            None => {
+                let seed = options.seed.unwrap_or_else(Self::random_seed);
+                println!("Starting game with seed {}", seed);
+
+                let game_config = GameConfig {
+                    mode: options.mode,
+                    playfield_dimensions: options.playfield_dimensions,
+                    garbage_messiness: options.garbage_messiness,
+                    ..GameConfig::default()
+                };
+                game_config.validate()?;
+
+                (game_config, seed, TetrisInput::new())
+            }
+        };
+
         let render_state = RenderState::new(window);
-        Self {
+        Ok(Self {
             window,
             render_state: render_state,
 
-            playfield: Playfield::new_empty(),
-            state: TetrominoState::Spawning(0.0),
-            input: TetrisInput::new(),
+            game_config,
+            game: Game::new(game_config, seed),
+            replay: Replay::new(seed, game_config),
+            paused: false,
+            input,
 
             projection_matrix: Self::compute_projection(window_size.width as f32, window_size.height as f32),
+        })
+    }
+
+    // Throws away the current game and starts a new one. The window and render state are kept as they are.
+    // When playing a replay, the new game uses the replay's seed and rules, so the replay can be played from the start again.
+    fn restart(&mut self) {
+        let seed = match self.input.replay_player() {
+            Some(player) => player.replay().seed,
+            None => {
+                let seed = Self::random_seed();
+                println!("Starting game with seed {}", seed);
+                seed
+            }
+        };
+
+        self.game = Game::new(self.game_config, seed);
+        self.replay = Replay::new(seed, self.game_config);
+        self.paused = false;
+    }
+
+    fn random_seed() -> u64 {
+        thread_rng().gen()
+    }
+
+    fn pieces_per_second(&self) -> f64 {
+        if self.game.elapsed_time() > 0.0 {
+            self.game.pieces_locked() as f64 / self.game.elapsed_time()
+        }
+        else {
+            0.0
+        }
+    }
+
+    // If `clip_height` is set, blocks at or above that row aren't drawn. Used to hide the parts of pieces that are in the playfield's hidden rows.
+    fn render_tetromino(&mut self, view_projection_matrix: &Matrix4<f32>, tetromino: &Tetromino, alpha: u8, clip_height: Option<usize>) {
+        let tint_color = Self::with_alpha(tetromino.get_color(), alpha);
+
+        for cell in &tetromino.get_occupied_cells() {
+            if clip_height.map_or(false, |clip_height| cell.y >= clip_height as i8) {
+                continue;
+            }
+
+            let mvp_matrix = *view_projection_matrix * Self::block_model_matrix(cell.x as f32, cell.y as f32);
+
+            self.render_state.render_tetris_block(&mut self.window, &mvp_matrix, &tint_color);
         }
     }
 
+    // Draws a line of text horizontally centered on `center_x`, with the top of the text at `top_y`, in playfield cells
+    fn render_centered_text(&mut self, view_projection_matrix: &Matrix4<f32>, text: &str, center_x: f32, top_y: f32) {
+        let text_width = RenderState::text_width(text) * TEXT_SCALE;
+        let text_matrix: Matrix4<f32> = Decomposed::<Vector3<f32>, Quaternion<f32>> {
+            scale: TEXT_SCALE,
+            rot: Quaternion::one(),
+            disp: Vector3 { x: center_x - text_width / 2.0, y: top_y, z: 0.0 },
+        }.into();
+
+        self.render_state.render_text(&mut self.window, &(*view_projection_matrix * text_matrix), text, &Rgba(TEXT_COLOR));
+    }
+
+    // Draws the final stats over the top of the playfield. Finished sprints, ultras, marathons, and digs show the results of the run instead.
+    fn render_game_over(&mut self, view_projection_matrix: &Matrix4<f32>) {
+        let (playfield_width, playfield_height) = self.game.playfield().visible_dimensions();
+        let center_x = (playfield_width as f32 - 1.0) / 2.0;
+
+        let lines = match self.game.game_over_reason() {
+            Some(GameOverReason::LineGoalReached) | Some(GameOverReason::GarbageCleared) => vec![
+                "FINISHED".to_string(),
+                String::new(),
+                "TIME".to_string(),
+                format_time(self.game.elapsed_time()),
+                "PIECES".to_string(),
+                self.game.pieces_locked().to_string(),
+                "PPS".to_string(),
+                format!("{:.2}", self.pieces_per_second()),
+                String::new(),
+                "R TO RESTART".to_string(),
+            ],
+            Some(GameOverReason::TimeUp) => vec![
+                "TIME UP".to_string(),
+                String::new(),
+                "SCORE".to_string(),
+                self.game.scoring().score().to_string(),
+                "LINES".to_string(),
+                self.game.scoring().lines().to_string(),
+                "PIECES".to_string(),
+                self.game.pieces_locked().to_string(),
+                "PPS".to_string(),
+                format!("{:.2}", self.pieces_per_second()),
+                String::new(),
+                "R TO RESTART".to_string(),
+            ],
+            Some(GameOverReason::LevelCapReached) => vec![
+                "CLEARED".to_string(),
+                String::new(),
+                "SCORE".to_string(),
+                self.game.scoring().score().to_string(),
+                "LINES".to_string(),
+                self.game.scoring().lines().to_string(),
+                "TIME".to_string(),
+                format_time(self.game.elapsed_time()),
+                String::new(),
+                "R TO RESTART".to_string(),
+            ],
+            reason => {
+                let reason_text = match reason {
+                    Some(GameOverReason::BlockOut) => "BLOCK OUT",
+                    Some(GameOverReason::LockOut) => "LOCK OUT",
+                    Some(GameOverReason::TopOut) => "TOP OUT",
+                    _ => "",
+                };
+
+                vec![
+                    "GAME OVER".to_string(),
+                    reason_text.to_string(),
+                    String::new(),
+                    "SCORE".to_string(),
+                    self.game.scoring().score().to_string(),
+                    "LINES".to_string(),
+                    self.game.scoring().lines().to_string(),
+                    "LEVEL".to_string(),
+                    self.game.scoring().level().to_string(),
+                    String::new(),
+                    "R TO RESTART".to_string(),
+                ]
+            }
+        };
+
+        let line_height = RenderState::line_height() * TEXT_SCALE;
+        let mut top_y = playfield_height as f32 - PREVIEW_MARGIN as f32;
+        for line in lines.iter() {
+            self.render_centered_text(view_projection_matrix, line, center_x, top_y);
+            top_y -= line_height;
+        }
+    }
+
+    fn render_paused(&mut self, view_projection_matrix: &Matrix4<f32>) {
+        let (playfield_width, playfield_height) = self.game.playfield().visible_dimensions();
+        let center_x = (playfield_width as f32 - 1.0) / 2.0;
+        let center_y = playfield_height as f32 / 2.0;
+
+        self.render_centered_text(view_projection_matrix, "PAUSED", center_x, center_y);
+        self.render_centered_text(view_projection_matrix, "P TO RESUME", center_x, center_y - 2.0 * RenderState::line_height() * TEXT_SCALE);
+    }
+
+    // Shows the countdown in the middle of the playfield before a timed game starts, and the progress above the playfield in modes that end
+    fn render_mode_status(&mut self, view_projection_matrix: &Matrix4<f32>) {
+        let (playfield_width, playfield_height) = self.game.playfield().visible_dimensions();
+        let center_x = (playfield_width as f32 - 1.0) / 2.0;
+
+        if let Some(countdown) = self.game.countdown_remaining() {
+            let countdown_text = (countdown.ceil() as u32).to_string();
+            self.render_centered_text(view_projection_matrix, &countdown_text, center_x, playfield_height as f32 / 2.0);
+        }
+
+        if let Some(line_goal) = self.game.mode().line_goal() {
+            let status = format!("{}/{} {}", self.game.scoring().lines().min(line_goal), line_goal, format_time(self.game.elapsed_time()));
+            self.render_centered_text(view_projection_matrix, &status, center_x, playfield_height as f32 + 1.5);
+        }
+
+        if let Some(level_cap) = self.game.mode().level_cap() {
+            let status = format!("LEVEL {}/{}", self.game.scoring().level().min(level_cap), level_cap);
+            self.render_centered_text(view_projection_matrix, &status, center_x, playfield_height as f32 + 1.5);
+        }
+
+        if self.game.mode().starting_garbage_rows().is_some() {
+            let status = format!("{} LEFT {}", self.game.garbage_rows_left(), format_time(self.game.elapsed_time()));
+            self.render_centered_text(view_projection_matrix, &status, center_x, playfield_height as f32 + 1.5);
+        }
+
+        // ultra counts down the time that's left, rather than up
+        if let Some(time_limit) = self.game.mode().time_limit() {
+            let time_left = (time_limit as f64 - self.game.elapsed_time()).max(0.0);
+            let status = format!("{} {}", self.game.scoring().score(), format_time(time_left));
+            self.render_centered_text(view_projection_matrix, &status, center_x, playfield_height as f32 + 1.5);
+        }
+    }
+
+    // When playing a replay, shows the playback position underneath the playfield
+    fn render_replay_status(&mut self, view_projection_matrix: &Matrix4<f32>) {
+        let status = match self.input.replay_player() {
+            Some(player) if player.is_paused() => format!("REPLAY {}/{} PAUSED", player.position(), player.len()),
+            Some(player) => format!("REPLAY {}/{}", player.position(), player.len()),
+            None => return,
+        };
+
+        let (playfield_width, _) = self.game.playfield().visible_dimensions();
+        let center_x = (playfield_width as f32 - 1.0) / 2.0;
+        self.render_centered_text(view_projection_matrix, &status, center_x, -1.0);
+    }
+
+    fn with_alpha(color: Rgb<u8>, alpha: u8) -> Rgba<u8> {
+        Rgba([color[0], color[1], color[2], alpha])
+    }
+
+    fn block_model_matrix(x: f32, y: f32) -> Matrix4<f32> {
+        Decomposed::<Vector3<f32>, Quaternion<f32>> {
+            scale: 1.0,
+            rot: Quaternion::one(),
+            disp: Vector3 { x, y, z: 0.0 },
+        }.into()
+    }
+
+    // Frames the playfield along with the hold slot, the next queue, and the text underneath, so that boards of any size fill the window
+    fn compute_view(&self) -> Matrix4<f32> {
+        let (playfield_width, playfield_height) = self.game.playfield().visible_dimensions();
+        let preview_count = self.game.next_pieces().count() as f32;
+
+        // pieces in the hold slot and the next queue stick out up to 2 cells past their position
+        let left = -(PREVIEW_MARGIN as f32) - 3.0;
+        let right = playfield_width as f32 + PREVIEW_MARGIN as f32 + 3.0;
+        let top = playfield_height as f32 + FRAME_MARGIN;
+        let lowest_preview = playfield_height as f32 - PREVIEW_MARGIN as f32 - (preview_count - 1.0) * PREVIEW_SPACING as f32 - 1.0;
+        let bottom = lowest_preview.min(-FRAME_MARGIN);
+
+        // the projection always shows at least -1 to 1 on both axes, so fit the larger side into that
+        let scale = 2.0 / (right - left).max(top - bottom);
+        Decomposed::<Vector3<f32>, Quaternion<f32>> {
+            scale,
+            rot: Quaternion::one(),
+            disp: Vector3 { x: -scale * (left + right) / 2.0, y: -scale * (bottom + top) / 2.0, z: 0.0 },
+        }.into()
+    }
+
     fn compute_projection(width: f32, height: f32) -> Matrix4<f32> {
         if width > height {
             let ratio = width / height;
@@ -79,41 +346,53 @@ impl<'app> App for TetrisApp<'app> {
     fn render(&mut self, _: &RenderArgs) {
         self.window.encoder.clear(&self.window.output_color, [0.0, 0.0, 0.0, 1.0]);
 
-        let view_matrix: Matrix4<f32> = Decomposed::<Vector3<f32>, Quaternion<f32>> {
-            scale: 0.05,
-            rot: Quaternion::one(),
-            disp: Vector3::zero(),
-        }.into();
-        let view_projection_matrix = self.projection_matrix * view_matrix;
+        let view_projection_matrix = self.projection_matrix * self.compute_view();
 
-        // render any orphan blocks
-        for (cell, orphan) in self.playfield.iter_orphans() {
-            let model_matrix: Matrix4<f32> = Decomposed::<Vector3<f32>, Quaternion<f32>> {
-                scale: 1.0,
-                rot: Quaternion::one(),
-                disp: Vector3 { x: cell.x as f32, y: cell.y as f32, z: 0.0 },
-            }.into();
-            let mvp_matrix = view_projection_matrix * model_matrix;
+        // while paused, hide the board and upcoming pieces, so the player can't pause to plan their next moves
+        if self.paused {
+            self.render_paused(&view_projection_matrix);
+            self.window.encoder.flush(&mut self.window.device);
+            return;
+        }
+
+        // render any orphan blocks. If the game is over, dim them so the final stats stand out
+        let orphan_alpha = if self.game.is_game_over() { DIMMED_ALPHA } else { OPAQUE_ALPHA };
+        let (playfield_width, playfield_height) = self.game.playfield().visible_dimensions();
+        for (cell, orphan) in self.game.playfield().iter_orphans().filter(|&(cell, _)| cell.y < playfield_height as i8) {
+            let mvp_matrix = view_projection_matrix * Self::block_model_matrix(cell.x as f32, cell.y as f32);
 
-            self.render_state.render_tetris_block(&mut self.window, &mvp_matrix, &orphan.color);
+            self.render_state.render_tetris_block(&mut self.window, &mvp_matrix, &Self::with_alpha(orphan.color, orphan_alpha));
         }
 
-        // render the active piece, if present
-        if let TetrominoState::Active(ref active_tetromino, _) = self.state {
-            let piece_color = active_tetromino.get_color();
+        // render the active piece, if present, along with a ghost piece at the position it would hard drop to. The ghost goes first so that the active piece draws over it when they overlap.
+        if let (Some(active_tetromino), Some(ghost_tetromino)) = (self.game.active_tetromino(), self.game.ghost_tetromino()) {
+            self.render_tetromino(&view_projection_matrix, &ghost_tetromino, GHOST_ALPHA, Some(playfield_height));
+            self.render_tetromino(&view_projection_matrix, &active_tetromino, OPAQUE_ALPHA, Some(playfield_height));
+        }
 
-            for cell in &active_tetromino.get_occupied_cells() {
-                let model_matrix: Matrix4<f32> = Decomposed::<Vector3<f32>, Quaternion<f32>> {
-                    scale: 1.0,
-                    rot: Quaternion::one(),
-                    disp: Vector3 { x: cell.x as f32, y: cell.y as f32, z: 0.0 },
-                }.into();
-                let mvp_matrix = view_projection_matrix * model_matrix;
+        // render the upcoming pieces in a column to the right of the playfield
+        let preview_pieces: Vec<PieceType> = self.game.next_pieces().cloned().collect();
+        for (index, piece_type) in preview_pieces.into_iter().enumerate() {
+            let preview_position = IntVector2::new(
+                playfield_width as i8 + PREVIEW_MARGIN,
+                playfield_height as i8 - PREVIEW_MARGIN - (index as i8) * PREVIEW_SPACING,
+            );
+            self.render_tetromino(&view_projection_matrix, &Tetromino::new(piece_type, preview_position), OPAQUE_ALPHA, None);
+        }
 
-                self.render_state.render_tetris_block(&mut self.window, &mvp_matrix, &piece_color);
-            }
+        // render the held piece to the left of the playfield
+        if let Some(held_piece) = self.game.held_piece() {
+            let hold_position = IntVector2::new(-PREVIEW_MARGIN - 1, playfield_height as i8 - PREVIEW_MARGIN);
+            self.render_tetromino(&view_projection_matrix, &Tetromino::new(held_piece, hold_position), OPAQUE_ALPHA, None);
         }
 
+        if self.game.is_game_over() {
+            self.render_game_over(&view_projection_matrix);
+        }
+
+        self.render_mode_status(&view_projection_matrix);
+        self.render_replay_status(&view_projection_matrix);
+
         self.window.encoder.flush(&mut self.window.device);
     }
     fn after_render(&mut self, _: &AfterRenderArgs) {
@@ -127,70 +406,69 @@ impl<'app> App for TetrisApp<'app> {
         // update our input axes
         self.input.update(dt, input_events);
 
-        // If we're waiting to spawn a new tetromino, update the cooldown
-        if let TetrominoState::Spawning(ref mut spawn_cooldown) = self.state {
-            *spawn_cooldown -= dt;
-            if *spawn_cooldown < 0.0 {
-                let new_tetromino = Tetromino::new_random(self.playfield.spawn_location(), &mut thread_rng());
-                if !self.playfield.is_valid_placement(&new_tetromino) {
-                    self.state = TetrominoState::GameOver;
-                }
-                else {
-                    self.state = TetrominoState::Active(new_tetromino, 0.0)
-                }
-            }
+        // Pause or unpause when the player asks, and pause automatically if the window loses focus. Games that are already over can't be paused.
+        // During replays, the P key pauses playback instead.
+        if self.game.is_game_over() || self.input.is_replay() {
+            self.paused = false;
         }
-
-        // If we have an active tetromino, update its position from gravity
-        if let TetrominoState::Active(ref mut active_tetromino, ref mut drop_cooldown) = self.state {
-            *drop_cooldown -= dt;
-
-            // If we've hit the drop cooldown, move this piece down by 1 row. If we can't, lock it in place.
-            if *drop_cooldown < 0.0 {
-                let moved_tetromino = active_tetromino.moved(IntVector2::new(0, -1));
-                if self.playfield.is_valid_placement(&moved_tetromino) {
-                    *active_tetromino = moved_tetromino;
-                    *drop_cooldown = 1.0;
-                }
-                else {
-                    self.playfield.lock_tetromino(&active_tetromino);
-                    self.state = TetrominoState::Spawning(1.0)
-                }
-            }
+        else if self.input.key_p.pressed_this_frame() {
+            self.paused = !self.paused;
+        }
+        else if self.input.focus_lost_this_frame {
+            self.paused = true;
         }
 
-        // If we STILL have an active tetromino after dropping, handle player input
-        if let TetrominoState::Active(ref mut active_tetromino, _) = self.state {
+        // While paused, the game doesn't update at all, so gravity, spawn cooldowns, and lock delays all stay frozen
+        if self.paused {
+            return;
+        }
 
-            // try to move the tetromino left
-            if self.input.arrow_left.pressed_this_frame() {
-                let updated_tetromino = active_tetromino.moved(IntVector2::new(-1, 0));
-                if self.playfield.is_valid_placement(&updated_tetromino) {
-                    *active_tetromino = updated_tetromino;
-                }
+        // If the game is over, the only thing the player can do is start a new one. Replays keep going, so that the viewer can seek backwards.
+        if self.game.is_game_over() && !self.input.is_replay() {
+            if self.input.key_r.pressed_this_frame() {
+                self.restart();
             }
+            return;
+        }
 
-            // try to move the tetromino right
-            if self.input.arrow_right.pressed_this_frame() {
-                let updated_tetromino = active_tetromino.moved(IntVector2::new(1, 0));
-                if self.playfield.is_valid_placement(&updated_tetromino) {
-                    *active_tetromino = updated_tetromino;
-                }
-            }
+        // seeking backwards in a replay means replaying it from the start
+        let input_frames = self.input.game_frames(dt);
+        if input_frames.restart {
+            self.restart();
+        }
 
-            // try to rotate the tetromino left
-            if self.input.key_a.pressed_this_frame() {
-                let updated_tetromino = active_tetromino.rotated_left();
-                if self.playfield.is_valid_placement(&updated_tetromino) {
-                    *active_tetromino = updated_tetromino;
-                }
+        for frame in input_frames.frames {
+            // record every frame's input, so the whole game can be replayed later
+            let recording = !self.input.is_replay();
+            if recording {
+                self.replay.record_frame(frame.dt, frame.input);
             }
 
-            // try to rotate the tetromino right
-            if self.input.key_d.pressed_this_frame() {
-                let updated_tetromino = active_tetromino.rotated_right();
-                if self.playfield.is_valid_placement(&updated_tetromino) {
-                    *active_tetromino = updated_tetromino;
+            for event in self.game.update(frame.dt, &frame.input) {
+                if let GameEvent::GameOver(reason) = event {
+                    let scoring = self.game.scoring();
+                    if reason == GameOverReason::LineGoalReached {
+                        println!("Sprint finished! Seed: {}, time: {}, pieces: {}, PPS: {:.2}", self.game.seed(), format_time(self.game.elapsed_time()), self.game.pieces_locked(), self.pieces_per_second());
+                    }
+                    else if reason == GameOverReason::GarbageCleared {
+                        println!("Dig finished! Seed: {}, time: {}, pieces: {}, PPS: {:.2}", self.game.seed(), format_time(self.game.elapsed_time()), self.game.pieces_locked(), self.pieces_per_second());
+                    }
+                    else if reason == GameOverReason::LevelCapReached {
+                        println!("Marathon cleared! Seed: {}, score: {}, lines: {}, time: {}", self.game.seed(), scoring.score(), scoring.lines(), format_time(self.game.elapsed_time()));
+                    }
+                    else if reason == GameOverReason::TimeUp {
+                        println!("Time up! Seed: {}, score: {}, lines: {}, pieces: {}, PPS: {:.2}", self.game.seed(), scoring.score(), scoring.lines(), self.game.pieces_locked(), self.pieces_per_second());
+                    }
+                    else {
+                        println!("Game over ({:?})! Seed: {}, score: {}, lines: {}, level: {}", reason, self.game.seed(), scoring.score(), scoring.lines(), scoring.level());
+                    }
+
+                    if recording {
+                        match self.replay.save() {
+                            Ok(path) => println!("Saved replay to {}", path.display()),
+                            Err(error) => println!("Failed to save replay: {}", error),
+                        }
+                    }
                 }
             }
         }
@@ -204,4 +482,10 @@ impl<'app> App for TetrisApp<'app> {
         //rebuild the entire render state. TODO find a way to update each thing's render target without doing this or making the render state mutable
         self.render_state = RenderState::new(&mut self.window);
     }
-}
\ No newline at end of file
+}
+
+// Formats a time in seconds as minutes, seconds, and milliseconds, like 1:23.456
+fn format_time(seconds: f64) -> String {
+    let millis = (seconds * 1000.0).round() as u64;
+    format!("{}:{:02}.{:03}", millis / 60000, millis / 1000 % 60, millis % 1000)
+}
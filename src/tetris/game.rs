@@ -0,0 +1,1001 @@
+use rand::SeedableRng;
+use rand::isaac::Isaac64Rng;
+
+use ::engine::{AutoRepeat, BinaryAxis};
+use ::engine::intvector::IntVector2;
+
+use super::tetromino::{Tetromino, PieceType};
+use super::playfield::{Playfield, PlayfieldDimensions, TSpin};
+use super::wall_kicks;
+use super::wall_kicks::KickedRotation;
+use super::piece_generator::SevenBagGenerator;
+use super::next_queue::{NextQueue, DEFAULT_NEXT_QUEUE_LENGTH, MAX_NEXT_QUEUE_LENGTH};
+use super::scoring::{Scoring, MAX_STARTING_LEVEL};
+use super::streaks::ClearStreaks;
+use super::game_mode::{GameMode, COUNTDOWN_TIME};
+use super::garbage;
+use super::garbage::{GarbageGenerator, DEFAULT_GARBAGE_MESSINESS};
+
+// how long to wait after locking a piece before spawning the next one
+const SPAWN_DELAY: f32 = 1.0;
+
+// extra time added to the spawn delay when the lock cleared some rows, so the player can see the board collapse
+const LINE_CLEAR_DELAY: f32 = 0.5;
+
+// mixed into the seed for the garbage generator, so garbage doesn't change which pieces are dealt
+const GARBAGE_SEED_SALT: u64 = 0x6761_7262_6167_6521;
+
+// holding soft drop always moves the piece at least this many times faster than gravity
+const SOFT_DROP_GRAVITY_MULTIPLIER: f32 = 20.0;
+
+// how long a piece can rest on the stack before it locks in place
+const LOCK_DELAY: f32 = 0.5;
+
+// upper limits for the handling settings in GameConfig. Anything slower than a second per move, or more resets than this, stops being Tetris
+const MAX_HANDLING_MILLIS: u32 = 1000;
+const MAX_LOCK_RESETS: u32 = 30;
+
+// Everything about the rules of a game that can be tweaked before it starts
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GameConfig {
+    pub mode: GameMode,
+    pub playfield_dimensions: PlayfieldDimensions,
+    pub next_queue_length: usize,
+    pub starting_level: u32,
+
+    // delayed auto shift and auto repeat rate for moving left and right, in milliseconds. An ARR of 0 moves the piece all the way to the wall once DAS has charged.
+    pub das_millis: u32,
+    pub arr_millis: u32,
+
+    // soft drop moves the piece down one row immediately, then repeats at this interval, in milliseconds.
+    // At high levels the interval shrinks further, so soft drop stays faster than gravity.
+    pub soft_drop_millis: u32,
+
+    // how many times moving or rotating a resting piece can restart its lock delay, before the piece reaches a new lowest row
+    pub max_lock_resets: u32,
+
+    // chance, from 0 to 1, that each incoming garbage row has its hole in a different column to the row below
+    pub garbage_messiness: f32,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            mode: GameMode::default(),
+            playfield_dimensions: PlayfieldDimensions::default(),
+            next_queue_length: DEFAULT_NEXT_QUEUE_LENGTH,
+            starting_level: 1,
+            das_millis: 167,
+            arr_millis: 33,
+            soft_drop_millis: 50,
+            max_lock_resets: 15,
+            garbage_messiness: DEFAULT_GARBAGE_MESSINESS,
+        }
+    }
+}
+
+impl GameConfig {
+    // Checks that every setting is in a range the game can handle. Configs from the command line and from replay files both go through this,
+    // so a damaged or hand-edited replay can't ask for something like a million-piece next queue.
+    pub fn validate(&self) -> Result<(), String> {
+        self.playfield_dimensions.validate()?;
+        self.mode.validate(&self.playfield_dimensions)?;
+        garbage::validate_messiness(self.garbage_messiness)?;
+
+        if self.next_queue_length > MAX_NEXT_QUEUE_LENGTH {
+            return Err(format!("next queue length must be at most {}", MAX_NEXT_QUEUE_LENGTH));
+        }
+
+        if self.starting_level < 1 || self.starting_level > MAX_STARTING_LEVEL {
+            return Err(format!("starting level must be between 1 and {}", MAX_STARTING_LEVEL));
+        }
+
+        if let Some(level_cap) = self.mode.level_cap() {
+            if level_cap < self.starting_level {
+                return Err("level cap must be at least the starting level".to_string());
+            }
+        }
+
+        if self.das_millis > MAX_HANDLING_MILLIS {
+            return Err(format!("DAS must be at most {} milliseconds", MAX_HANDLING_MILLIS));
+        }
+
+        if self.arr_millis > MAX_HANDLING_MILLIS {
+            return Err(format!("ARR must be at most {} milliseconds", MAX_HANDLING_MILLIS));
+        }
+
+        if self.soft_drop_millis > MAX_HANDLING_MILLIS {
+            return Err(format!("soft drop interval must be at most {} milliseconds", MAX_HANDLING_MILLIS));
+        }
+
+        if self.max_lock_resets > MAX_LOCK_RESETS {
+            return Err(format!("lock resets must be at most {}", MAX_LOCK_RESETS));
+        }
+
+        Ok(())
+    }
+}
+
+// The logical buttons the game reacts to, independent of how they're mapped to keys. Each one is true if it's held down this frame.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct GameInput {
+    pub left: bool,
+    pub right: bool,
+    pub soft_drop: bool,
+    pub hard_drop: bool,
+    pub rotate_left: bool,
+    pub rotate_right: bool,
+    pub hold: bool,
+}
+
+impl GameInput {
+    // Packs the buttons into one bit each, for storing compactly in replays
+    pub fn to_bits(&self) -> u8 {
+        let buttons = [self.left, self.right, self.soft_drop, self.hard_drop, self.rotate_left, self.rotate_right, self.hold];
+
+        buttons.iter().enumerate().fold(0, |bits, (index, &held)| if held { bits | (1u8 << index) } else { bits })
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        let held = |index: u8| bits & (1u8 << index) != 0;
+
+        Self {
+            left: held(0),
+            right: held(1),
+            soft_drop: held(2),
+            hard_drop: held(3),
+            rotate_left: held(4),
+            rotate_right: held(5),
+            hold: held(6),
+        }
+    }
+}
+
+// Things that happened during an update, for the frontend to react to
+#[derive(Clone, Copy, Debug)]
+pub enum GameEvent {
+    PieceSpawned(PieceType),
+    PieceHeld(PieceType),
+    PieceLocked { lines_cleared: usize, t_spin: TSpin, combo: Option<u32>, back_to_back: bool },
+    LevelUp(u32),
+    GameOver(GameOverReason),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameOverReason {
+    // a new piece spawned overlapping the stack
+    BlockOut,
+
+    // a piece locked entirely above the visible part of the playfield
+    LockOut,
+
+    // the player cleared enough lines to finish a sprint
+    LineGoalReached,
+
+    // the clock ran out in a timed game
+    TimeUp,
+
+    // the player completed the last level of a marathon
+    LevelCapReached,
+
+    // incoming garbage pushed blocks off the top of the playfield
+    TopOut,
+
+    // the player cleared all of the garbage the board started with
+    GarbageCleared,
+}
+
+struct ActivePiece {
+    tetromino: Tetromino,
+    drop_cooldown: f32,
+
+    // counts down while the piece is resting on the stack. None while the piece is in the air
+    lock_timer: Option<f32>,
+    lock_resets: u32,
+    lowest_row: i8,
+
+    // if the piece's last successful move was a rotation, this is the kick index that rotation used. Needed for T-spin detection.
+    last_rotation_kick: Option<usize>,
+}
+
+impl ActivePiece {
+    fn new(tetromino: Tetromino) -> Self {
+        Self {
+            tetromino,
+            drop_cooldown: 0.0,
+            lock_timer: None,
+            lock_resets: 0,
+            lowest_row: tetromino.position().y,
+            last_rotation_kick: None,
+        }
+    }
+
+    // Moves the piece down by one row. Reaching a new lowest row gives the player a fresh set of lock resets.
+    fn fall(&mut self, fallen_tetromino: Tetromino) {
+        self.tetromino = fallen_tetromino;
+        self.last_rotation_kick = None;
+
+        if fallen_tetromino.position().y < self.lowest_row {
+            self.lowest_row = fallen_tetromino.position().y;
+            self.lock_resets = 0;
+        }
+    }
+
+    // Moves or rotates the piece at the player's request. If the piece is resting on the stack, this restarts its lock delay.
+    fn shift(&mut self, shifted_tetromino: Tetromino, max_lock_resets: u32) {
+        self.tetromino = shifted_tetromino;
+        self.last_rotation_kick = None;
+
+        if self.lock_timer.is_some() && self.lock_resets < max_lock_resets {
+            self.lock_timer = Some(LOCK_DELAY);
+            self.lock_resets += 1;
+        }
+    }
+
+    // Rotates the piece at the player's request. Same as shift, except it remembers the kick so we can check for T-spins on lock.
+    fn rotate(&mut self, kicked_rotation: KickedRotation, max_lock_resets: u32) {
+        self.shift(kicked_rotation.tetromino, max_lock_resets);
+        self.last_rotation_kick = Some(kicked_rotation.kick_index);
+    }
+}
+
+enum TetrominoState {
+    Countdown(f32),
+    Spawning(f32),
+    Active(ActivePiece),
+    GameOver(GameOverReason),
+}
+
+// One game of tetris: the playfield, the pieces, the timers, and the score. Doesn't know anything about windows, keyboards, or rendering --
+// it's driven entirely by calling `update` with the elapsed time and the logical inputs for each frame.
+pub struct Game {
+    config: GameConfig,
+
+    // every random decision in the game comes from this seed, so the same seed and the same inputs always produce the same game
+    seed: u64,
+
+    playfield: Playfield,
+    next_queue: NextQueue,
+    garbage: GarbageGenerator<Isaac64Rng>,
+    held_piece: Option<PieceType>,
+    hold_available: bool,
+    state: TetrominoState,
+    scoring: Scoring,
+    streaks: ClearStreaks,
+
+    // seconds since the game started, not counting the countdown. Stops when the game ends.
+    // Kept as an f64 so that adding up thousands of small frame times doesn't lose precision.
+    elapsed_time: f64,
+    pieces_locked: u32,
+
+    // the game tracks its own button states, so that it can tell when buttons were pressed and how long they've been held
+    left: BinaryAxis,
+    right: BinaryAxis,
+    soft_drop: BinaryAxis,
+    hard_drop: BinaryAxis,
+    rotate_left: BinaryAxis,
+    rotate_right: BinaryAxis,
+    hold: BinaryAxis,
+
+    shift_repeat: AutoRepeat,
+
+    // events that have happened so far during the current update
+    events: Vec<GameEvent>,
+}
+
+impl Game {
+    pub fn new(config: GameConfig, seed: u64) -> Self {
+        let rng = Isaac64Rng::from_seed(&[seed][..]);
+
+        let mut game = Self {
+            config,
+            seed,
+
+            playfield: Playfield::new_empty(config.playfield_dimensions),
+            next_queue: NextQueue::new(Box::new(SevenBagGenerator::new(rng)), config.next_queue_length),
+            garbage: GarbageGenerator::new(Isaac64Rng::from_seed(&[seed, GARBAGE_SEED_SALT][..]), config.garbage_messiness),
+            held_piece: None,
+            hold_available: true,
+            state: if config.mode.has_countdown() { TetrominoState::Countdown(COUNTDOWN_TIME) } else { TetrominoState::Spawning(0.0) },
+            scoring: Scoring::new(config.starting_level),
+            streaks: ClearStreaks::new(),
+
+            elapsed_time: 0.0,
+            pieces_locked: 0,
+
+            left: BinaryAxis::new(),
+            right: BinaryAxis::new(),
+            soft_drop: BinaryAxis::new(),
+            hard_drop: BinaryAxis::new(),
+            rotate_left: BinaryAxis::new(),
+            rotate_right: BinaryAxis::new(),
+            hold: BinaryAxis::new(),
+
+            shift_repeat: AutoRepeat::from_millis(config.das_millis, config.arr_millis),
+
+            events: Vec::new(),
+        };
+
+        if let Some(garbage_rows) = config.mode.starting_garbage_rows() {
+            game.push_garbage(garbage_rows as usize, true);
+        }
+
+        game
+    }
+
+    #[allow(unused)]
+    pub fn config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn playfield(&self) -> &Playfield {
+        &self.playfield
+    }
+
+    pub fn scoring(&self) -> &Scoring {
+        &self.scoring
+    }
+
+    #[allow(unused)]
+    pub fn streaks(&self) -> &ClearStreaks {
+        &self.streaks
+    }
+
+    pub fn held_piece(&self) -> Option<PieceType> {
+        self.held_piece
+    }
+
+    pub fn next_pieces(&self) -> impl Iterator<Item=&PieceType> {
+        self.next_queue.iter()
+    }
+
+    pub fn active_tetromino(&self) -> Option<Tetromino> {
+        match self.state {
+            TetrominoState::Active(ref active_piece) => Some(active_piece.tetromino),
+            _ => None,
+        }
+    }
+
+    // where the active tetromino would end up if it was hard dropped right now
+    pub fn ghost_tetromino(&self) -> Option<Tetromino> {
+        self.active_tetromino().map(|tetromino| self.playfield.hard_drop_position(&tetromino))
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.config.mode
+    }
+
+    pub fn elapsed_time(&self) -> f64 {
+        self.elapsed_time
+    }
+
+    pub fn pieces_locked(&self) -> u32 {
+        self.pieces_locked
+    }
+
+    // seconds left before the game starts, if it's still counting down
+    pub fn countdown_remaining(&self) -> Option<f32> {
+        match self.state {
+            TetrominoState::Countdown(countdown) => Some(countdown),
+            _ => None,
+        }
+    }
+
+    pub fn game_over_reason(&self) -> Option<GameOverReason> {
+        match self.state {
+            TetrominoState::GameOver(reason) => Some(reason),
+            _ => None,
+        }
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        match self.state {
+            TetrominoState::GameOver(_) => true,
+            _ => false,
+        }
+    }
+
+    // Pushes `rows` garbage rows up from the bottom of the playfield. If the active piece would overlap the risen stack, it gets pushed up too.
+    // Ends the game if anything is pushed off the top. Any resulting events are returned from the next update.
+    #[allow(unused)]
+    pub fn add_garbage(&mut self, rows: usize) {
+        self.push_garbage(rows, false);
+    }
+
+    // how many rows of the garbage the game started with are still left to clear
+    pub fn garbage_rows_left(&self) -> usize {
+        self.playfield.tracked_garbage_rows()
+    }
+
+    fn push_garbage(&mut self, rows: usize, tracked: bool) {
+        if self.is_game_over() || rows == 0 {
+            return;
+        }
+
+        let hole_columns = self.garbage.hole_columns(rows, self.playfield.dimensions().width);
+        let mut topped_out = self.playfield.push_garbage(&hole_columns, tracked);
+
+        if let TetrominoState::Active(ref mut active_piece) = self.state {
+            let mut raised_tetromino = active_piece.tetromino;
+            for _ in 0..rows {
+                if self.playfield.is_valid_placement(&raised_tetromino) {
+                    break;
+                }
+                raised_tetromino = raised_tetromino.moved(IntVector2::new(0, 1));
+            }
+
+            if self.playfield.is_valid_placement(&raised_tetromino) {
+                active_piece.tetromino = raised_tetromino;
+            }
+            else {
+                topped_out = true;
+            }
+        }
+
+        if topped_out {
+            self.end_game(GameOverReason::TopOut);
+        }
+    }
+
+    // Advances the game by `dt` seconds, with the buttons in `input` held down. Returns everything that happened during the update, in order.
+    pub fn update(&mut self, dt: f32, input: &GameInput) -> Vec<GameEvent> {
+        self.update_buttons(dt, input);
+
+        // Nothing happens until the countdown runs out, and the clock doesn't start until then either.
+        // Buttons are still tracked during the countdown, so the player can charge DAS before the first piece appears.
+        if let TetrominoState::Countdown(ref mut countdown) = self.state {
+            *countdown -= dt;
+            if *countdown <= 0.0 {
+                self.state = TetrominoState::Spawning(0.0);
+            }
+        }
+        else if !self.is_game_over() {
+            self.elapsed_time += dt as f64;
+
+            // In timed games, stop as soon as the clock runs out, even in the middle of a piece. The active piece is discarded rather than locked.
+            if let Some(time_limit) = self.config.mode.time_limit() {
+                if self.elapsed_time >= time_limit as f64 {
+                    self.elapsed_time = time_limit as f64;
+                    self.end_game(GameOverReason::TimeUp);
+                }
+            }
+        }
+
+        // If we're waiting to spawn a new tetromino, update the cooldown
+        if let TetrominoState::Spawning(ref mut spawn_cooldown) = self.state {
+            *spawn_cooldown -= dt;
+            if *spawn_cooldown < 0.0 {
+                let piece_type = self.next_queue.pop();
+                self.spawn_tetromino(piece_type);
+            }
+        }
+
+        // If the player wants to hold, swap the active piece into the hold slot. This is only allowed once per piece.
+        if let TetrominoState::Active(ref active_piece) = self.state {
+            if self.hold.pressed_this_frame() && self.hold_available {
+                let next_piece = match self.held_piece {
+                    Some(held_piece) => held_piece,
+                    None => self.next_queue.pop(),
+                };
+
+                let held_piece = active_piece.tetromino.piece_type();
+                self.held_piece = Some(held_piece);
+                self.hold_available = false;
+                self.events.push(GameEvent::PieceHeld(held_piece));
+                self.spawn_tetromino(next_piece);
+            }
+        }
+
+        // If the player wants to hard drop, move the active tetromino all the way down and lock it immediately
+        if let TetrominoState::Active(ref active_piece) = self.state {
+            if self.hard_drop.pressed_this_frame() {
+                let dropped_tetromino = self.playfield.hard_drop_position(&active_piece.tetromino);
+                let drop_distance = active_piece.tetromino.position().y - dropped_tetromino.position().y;
+
+                // dropping the piece any distance means its last move wasn't a rotation anymore
+                let rotation_kick = if drop_distance > 0 { None } else { active_piece.last_rotation_kick };
+
+                self.scoring.award_hard_drop(drop_distance as u32);
+                self.lock_tetromino(&dropped_tetromino, rotation_kick);
+            }
+        }
+
+        // If we have an active tetromino, update its position from gravity
+        if let TetrominoState::Active(ref mut active_piece) = self.state {
+            active_piece.drop_cooldown -= dt;
+
+            // Every time we hit the drop cooldown, move this piece down by 1 row. At high levels, this can happen several times per frame.
+            // Once the piece lands on something, gravity stops accumulating and the lock delay takes over.
+            while active_piece.drop_cooldown < 0.0 {
+                let moved_tetromino = active_piece.tetromino.moved(IntVector2::new(0, -1));
+                if self.playfield.is_valid_placement(&moved_tetromino) {
+                    active_piece.fall(moved_tetromino);
+                    active_piece.drop_cooldown += self.scoring.gravity_delay();
+                }
+                else {
+                    active_piece.drop_cooldown = 0.0;
+                }
+            }
+        }
+
+        // If we STILL have an active tetromino after dropping, handle player input
+        if let TetrominoState::Active(ref mut active_piece) = self.state {
+            let max_lock_resets = self.config.max_lock_resets;
+
+            // try to soft drop the tetromino, once per auto repeat trigger
+            let soft_drop_interval = (self.config.soft_drop_millis as f32 / 1000.0).min(self.scoring.gravity_delay() / SOFT_DROP_GRAVITY_MULTIPLIER);
+            let soft_drop_repeat = AutoRepeat::new(soft_drop_interval, soft_drop_interval);
+            for _ in 0..soft_drop_repeat.triggers_this_frame(&self.soft_drop) {
+                let updated_tetromino = active_piece.tetromino.moved(IntVector2::new(0, -1));
+                if self.playfield.is_valid_placement(&updated_tetromino) {
+                    active_piece.fall(updated_tetromino);
+                    self.scoring.award_soft_drop(1);
+                }
+                else {
+                    break;
+                }
+            }
+
+            // try to move the tetromino left, once per auto repeat trigger
+            for _ in 0..self.shift_repeat.triggers_this_frame(&self.left) {
+                let updated_tetromino = active_piece.tetromino.moved(IntVector2::new(-1, 0));
+                if self.playfield.is_valid_placement(&updated_tetromino) {
+                    active_piece.shift(updated_tetromino, max_lock_resets);
+                }
+                else {
+                    break;
+                }
+            }
+
+            // try to move the tetromino right, once per auto repeat trigger
+            for _ in 0..self.shift_repeat.triggers_this_frame(&self.right) {
+                let updated_tetromino = active_piece.tetromino.moved(IntVector2::new(1, 0));
+                if self.playfield.is_valid_placement(&updated_tetromino) {
+                    active_piece.shift(updated_tetromino, max_lock_resets);
+                }
+                else {
+                    break;
+                }
+            }
+
+            // try to rotate the tetromino left, kicking it away from walls and the stack if it doesn't fit
+            if self.rotate_left.pressed_this_frame() {
+                let updated_tetromino = active_piece.tetromino.rotated_left();
+                if let Some(kicked_rotation) = wall_kicks::rotate_with_kicks(&self.playfield, &active_piece.tetromino, &updated_tetromino) {
+                    active_piece.rotate(kicked_rotation, max_lock_resets);
+                }
+            }
+
+            // try to rotate the tetromino right, kicking it away from walls and the stack if it doesn't fit
+            if self.rotate_right.pressed_this_frame() {
+                let updated_tetromino = active_piece.tetromino.rotated_right();
+                if let Some(kicked_rotation) = wall_kicks::rotate_with_kicks(&self.playfield, &active_piece.tetromino, &updated_tetromino) {
+                    active_piece.rotate(kicked_rotation, max_lock_resets);
+                }
+            }
+        }
+
+        // If the active tetromino is resting on something, count down its lock delay, and lock it in place once the delay runs out
+        if let TetrominoState::Active(ref mut active_piece) = self.state {
+            let below_tetromino = active_piece.tetromino.moved(IntVector2::new(0, -1));
+            if self.playfield.is_valid_placement(&below_tetromino) {
+                active_piece.lock_timer = None;
+            }
+            else {
+                // A piece that touches down again after using up its lock resets locks straight away. Otherwise kicking it up off the stack
+                // and letting it land would restart the lock delay forever. Reaching a new lowest row clears the resets, so this never applies there.
+                let starting_timer = match active_piece.lock_timer {
+                    Some(lock_timer) => lock_timer,
+                    None if active_piece.lock_resets >= self.config.max_lock_resets => 0.0,
+                    None => LOCK_DELAY,
+                };
+
+                let lock_timer = starting_timer - dt;
+                if lock_timer > 0.0 {
+                    active_piece.lock_timer = Some(lock_timer);
+                }
+                else {
+                    let locked_tetromino = active_piece.tetromino;
+                    let rotation_kick = active_piece.last_rotation_kick;
+                    self.lock_tetromino(&locked_tetromino, rotation_kick);
+                }
+            }
+        }
+
+        ::std::mem::replace(&mut self.events, Vec::new())
+    }
+
+    fn update_buttons(&mut self, dt: f32, input: &GameInput) {
+        self.left.set_held(input.left);
+        self.right.set_held(input.right);
+        self.soft_drop.set_held(input.soft_drop);
+        self.hard_drop.set_held(input.hard_drop);
+        self.rotate_left.set_held(input.rotate_left);
+        self.rotate_right.set_held(input.rotate_right);
+        self.hold.set_held(input.hold);
+
+        self.left.update(dt);
+        self.right.update(dt);
+        self.soft_drop.update(dt);
+        self.hard_drop.update(dt);
+        self.rotate_left.update(dt);
+        self.rotate_right.update(dt);
+        self.hold.update(dt);
+    }
+
+    fn spawn_tetromino(&mut self, piece_type: PieceType) {
+        let new_tetromino = Tetromino::new(piece_type, self.playfield.spawn_location(piece_type));
+        if !self.playfield.is_valid_placement(&new_tetromino) {
+            self.end_game(GameOverReason::BlockOut);
+        }
+        else {
+            self.state = TetrominoState::Active(ActivePiece::new(new_tetromino));
+            self.events.push(GameEvent::PieceSpawned(piece_type));
+        }
+    }
+
+    fn lock_tetromino(&mut self, tetromino: &Tetromino, rotation_kick: Option<usize>) {
+        // check this before locking, since clearing rows could move the piece's blocks down into view
+        let locked_out = self.playfield.is_above_visible_area(tetromino);
+
+        let lock_result = self.playfield.lock_tetromino(tetromino, rotation_kick);
+        self.pieces_locked += 1;
+
+        // now that this piece has locked, the next one is allowed to use the hold slot
+        self.hold_available = true;
+
+        let previous_level = self.scoring.level();
+        let streak_report = self.streaks.record_lock(&lock_result);
+        self.scoring.award_line_clear(lock_result.lines_cleared(), lock_result.t_spin, &streak_report);
+
+        self.events.push(GameEvent::PieceLocked {
+            lines_cleared: lock_result.lines_cleared(),
+            t_spin: lock_result.t_spin,
+            combo: streak_report.combo,
+            back_to_back: streak_report.back_to_back,
+        });
+
+        let line_goal_reached = self.config.mode.line_goal().map_or(false, |line_goal| self.scoring.lines() >= line_goal);
+        let level_cap_reached = self.config.mode.level_cap().map_or(false, |level_cap| self.scoring.level() > level_cap);
+        let garbage_cleared = self.config.mode.starting_garbage_rows().is_some() && self.playfield.tracked_garbage_rows() == 0;
+
+        // completing the final level of a marathon ends the game, rather than moving on to another level
+        if self.scoring.level() != previous_level && !level_cap_reached {
+            self.events.push(GameEvent::LevelUp(self.scoring.level()));
+        }
+
+        if locked_out {
+            self.end_game(GameOverReason::LockOut);
+        }
+        else if line_goal_reached {
+            self.end_game(GameOverReason::LineGoalReached);
+        }
+        else if level_cap_reached {
+            self.end_game(GameOverReason::LevelCapReached);
+        }
+        else if garbage_cleared {
+            self.end_game(GameOverReason::GarbageCleared);
+        }
+        else if lock_result.lines_cleared() > 0 {
+            self.state = TetrominoState::Spawning(SPAWN_DELAY + LINE_CLEAR_DELAY);
+        }
+        else {
+            self.state = TetrominoState::Spawning(SPAWN_DELAY);
+        }
+    }
+
+    fn end_game(&mut self, reason: GameOverReason) {
+        self.state = TetrominoState::GameOver(reason);
+        self.events.push(GameEvent::GameOver(reason));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Game, GameConfig, GameEvent, GameInput, GameOverReason, LOCK_DELAY};
+    use super::super::game_mode::GameMode;
+
+    const FRAME: f32 = 1.0 / 30.0;
+    const SEED: u64 = 12345;
+
+    fn idle() -> GameInput {
+        GameInput::default()
+    }
+
+    fn is_lock(event: &GameEvent) -> bool {
+        match *event {
+            GameEvent::PieceLocked { .. } => true,
+            _ => false,
+        }
+    }
+
+    fn is_hold(event: &GameEvent) -> bool {
+        match *event {
+            GameEvent::PieceHeld(_) => true,
+            _ => false,
+        }
+    }
+
+    fn is_level_up(event: &GameEvent) -> bool {
+        match *event {
+            GameEvent::LevelUp(_) => true,
+            _ => false,
+        }
+    }
+
+    // starts a game, and runs it until the first piece spawns
+    fn started_game() -> Game {
+        let mut game = Game::new(GameConfig::default(), SEED);
+        game.update(FRAME, &idle());
+
+        assert!(game.active_tetromino().is_some());
+        game
+    }
+
+    // a fixed, busy pattern of inputs, so that two games can be fed exactly the same frames
+    fn scripted_input(frame: usize) -> GameInput {
+        GameInput {
+            left: frame % 90 < 20,
+            right: frame % 90 >= 45 && frame % 90 < 60,
+            soft_drop: frame % 50 < 10,
+            hard_drop: frame % 60 == 59,
+            rotate_left: frame % 37 == 0,
+            rotate_right: frame % 23 == 0,
+            hold: frame % 200 == 100,
+        }
+    }
+
+    fn board(game: &Game) -> Vec<(i8, i8)> {
+        game.playfield().iter_orphans().map(|(cell, _)| (cell.x, cell.y)).collect()
+    }
+
+    fn is_resting(game: &Game) -> bool {
+        match (game.active_tetromino(), game.ghost_tetromino()) {
+            (Some(active_tetromino), Some(ghost_tetromino)) => active_tetromino.position().y == ghost_tetromino.position().y,
+            _ => false,
+        }
+    }
+
+    fn hard_drop() -> GameInput {
+        GameInput { hard_drop: true, ..GameInput::default() }
+    }
+
+    // runs the game until a piece spawns, skipping any countdown or spawn delay
+    fn wait_for_piece(game: &mut Game) {
+        let mut frames = 0;
+        while game.active_tetromino().is_none() {
+            assert!(frames < 1000, "no piece spawned");
+            game.update(FRAME, &idle());
+            frames += 1;
+        }
+    }
+
+    // fills in the row the active piece will land on, except where the piece will go, so hard dropping it clears that row
+    fn set_up_line_clear(game: &mut Game) {
+        let ghost_cells = game.ghost_tetromino().unwrap().get_occupied_cells();
+        let bottom_row = ghost_cells.iter().map(|cell| cell.y).min().unwrap();
+        let holes: Vec<usize> = ghost_cells.iter().filter(|cell| cell.y == bottom_row).map(|cell| cell.x as usize).collect();
+
+        game.playfield.fill_row(bottom_row as usize, &holes);
+    }
+
+    #[test]
+    fn hard_drop_locks_the_piece_at_the_bottom() {
+        let mut game = started_game();
+
+        let events = game.update(FRAME, &GameInput { hard_drop: true, ..GameInput::default() });
+
+        assert!(events.iter().any(is_lock));
+        assert!(game.active_tetromino().is_none());
+
+        let rows: Vec<i8> = game.playfield().iter_orphans().map(|(cell, _)| cell.y).collect();
+        assert_eq!(rows.len(), 4);
+        assert!(rows.iter().all(|&row| row <= 1));
+    }
+
+    #[test]
+    fn hold_is_only_allowed_once_per_piece() {
+        let mut game = started_game();
+        let hold = GameInput { hold: true, ..GameInput::default() };
+        let first_piece = game.active_tetromino().unwrap().piece_type();
+
+        let events = game.update(FRAME, &hold);
+        assert!(events.iter().any(is_hold));
+        assert_eq!(game.held_piece(), Some(first_piece));
+        let second_piece = game.active_tetromino().unwrap().piece_type();
+
+        // release and press hold again: the swapped in piece can't go back
+        game.update(FRAME, &idle());
+        let events = game.update(FRAME, &hold);
+        assert!(!events.iter().any(is_hold));
+        assert_eq!(game.held_piece(), Some(first_piece));
+        assert_eq!(game.active_tetromino().unwrap().piece_type(), second_piece);
+
+        // once that piece locks, the next one can use the hold slot again
+        game.update(FRAME, &GameInput { hard_drop: true, ..GameInput::default() });
+        while game.active_tetromino().is_none() {
+            game.update(FRAME, &idle());
+        }
+        let events = game.update(FRAME, &hold);
+        assert!(events.iter().any(is_hold));
+    }
+
+    #[test]
+    fn resting_piece_locks_when_lock_delay_runs_out() {
+        let mut game = started_game();
+
+        // let gravity bring the piece down onto the floor. The lock delay starts counting on the frame it lands.
+        let mut frames = 0;
+        while !is_resting(&game) {
+            assert!(frames < 10000, "piece never landed");
+            assert!(!game.update(FRAME, &idle()).iter().any(is_lock));
+            frames += 1;
+        }
+
+        // just short of the lock delay, the piece is still active
+        let frames_before_lock = (LOCK_DELAY / FRAME) as usize - 2;
+        for _ in 0..frames_before_lock {
+            assert!(!game.update(FRAME, &idle()).iter().any(is_lock));
+        }
+        assert!(game.active_tetromino().is_some());
+
+        // a few frames later, it's locked
+        let locked = (0..3).any(|_| game.update(FRAME, &idle()).iter().any(is_lock));
+        assert!(locked);
+        assert!(game.active_tetromino().is_none());
+    }
+
+    #[test]
+    fn same_seed_and_inputs_give_the_same_game() {
+        let mut first = Game::new(GameConfig::default(), SEED);
+        let mut second = Game::new(GameConfig::default(), SEED);
+
+        for frame in 0..3000 {
+            first.update(FRAME, &scripted_input(frame));
+            second.update(FRAME, &scripted_input(frame));
+        }
+
+        // make sure the script actually played the game, rather than comparing two empty boards
+        assert!(first.pieces_locked() > 0);
+
+        assert_eq!(first.next_pieces().collect::<Vec<_>>(), second.next_pieces().collect::<Vec<_>>());
+        assert_eq!(first.held_piece(), second.held_piece());
+        assert_eq!(board(&first), board(&second));
+        assert_eq!(first.scoring().score(), second.scoring().score());
+        assert_eq!(first.scoring().lines(), second.scoring().lines());
+        assert_eq!(first.pieces_locked(), second.pieces_locked());
+    }
+
+    #[test]
+    fn rotating_on_the_stack_cant_stall_the_lock_forever() {
+        let mut game = started_game();
+
+        // soft drop the piece onto the floor
+        let soft_drop = GameInput { soft_drop: true, ..GameInput::default() };
+        while !is_resting(&game) {
+            game.update(FRAME, &soft_drop);
+        }
+
+        // then keep tapping rotate, alternating directions. Every rotation tries to reset the lock delay, and some of them kick the piece up off the floor.
+        // Each new lowest row hands out a fresh set of resets, but the board is only so deep, so the piece has to lock well within this time.
+        let mut locked = false;
+        for frame in 0..(20.0 / FRAME) as usize {
+            let input = match frame % 4 {
+                0 => GameInput { rotate_right: true, ..GameInput::default() },
+                2 => GameInput { rotate_left: true, ..GameInput::default() },
+                _ => idle(),
+            };
+
+            if game.update(FRAME, &input).iter().any(is_lock) {
+                locked = true;
+                break;
+            }
+        }
+
+        assert!(locked);
+    }
+    #[test]
+    fn sprint_ends_when_the_line_goal_is_reached() {
+        let config = GameConfig { mode: GameMode::Sprint { line_goal: 1 }, ..GameConfig::default() };
+        let mut game = Game::new(config, SEED);
+        wait_for_piece(&mut game);
+
+        set_up_line_clear(&mut game);
+        game.update(FRAME, &hard_drop());
+
+        assert_eq!(game.scoring().lines(), 1);
+        assert_eq!(game.game_over_reason(), Some(GameOverReason::LineGoalReached));
+
+        // the clock stops with the game, so the final time is the time it took to clear the lines
+        let finish_time = game.elapsed_time();
+        assert!(finish_time > 0.0);
+        game.update(FRAME, &idle());
+        assert_eq!(game.elapsed_time(), finish_time);
+    }
+    #[test]
+    fn ultra_ends_when_the_time_runs_out() {
+        let config = GameConfig { mode: GameMode::Ultra { time_limit: 2 }, ..GameConfig::default() };
+        let mut game = Game::new(config, SEED);
+
+        let mut frames = 0;
+        while !game.is_game_over() {
+            assert!(frames < 1000, "time never ran out");
+            game.update(FRAME, &idle());
+            frames += 1;
+        }
+
+        // the last frame can overshoot the limit, but the clock stops exactly on it, and the piece that was still falling is discarded
+        assert_eq!(game.game_over_reason(), Some(GameOverReason::TimeUp));
+        assert_eq!(game.elapsed_time(), 2.0);
+        assert_eq!(game.pieces_locked(), 0);
+        assert!(game.active_tetromino().is_none());
+
+        game.update(FRAME, &idle());
+        assert_eq!(game.elapsed_time(), 2.0);
+    }
+    #[test]
+    fn marathon_ends_after_the_final_level_without_levelling_up() {
+        let config = GameConfig { mode: GameMode::Marathon { level_cap: 1 }, ..GameConfig::default() };
+        let mut game = Game::new(config, SEED);
+
+        // clear one line at a time, up to the line before the end of level 1
+        for _ in 0..9 {
+            wait_for_piece(&mut game);
+            set_up_line_clear(&mut game);
+
+            let events = game.update(FRAME, &hard_drop());
+            assert!(!events.iter().any(is_level_up));
+            assert!(!game.is_game_over());
+        }
+
+        wait_for_piece(&mut game);
+        set_up_line_clear(&mut game);
+        let events = game.update(FRAME, &hard_drop());
+
+        assert_eq!(game.scoring().lines(), 10);
+        assert_eq!(game.game_over_reason(), Some(GameOverReason::LevelCapReached));
+        assert!(!events.iter().any(is_level_up));
+    }
+    #[test]
+    fn dig_ends_when_the_starting_garbage_is_cleared() {
+        let config = GameConfig { mode: GameMode::Dig { garbage_rows: 1 }, ..GameConfig::default() };
+        let mut game = Game::new(config, SEED);
+        assert_eq!(game.garbage_rows_left(), 1);
+
+        // clearing a row above the garbage doesn't count
+        wait_for_piece(&mut game);
+        set_up_line_clear(&mut game);
+        game.update(FRAME, &hard_drop());
+
+        assert_eq!(game.scoring().lines(), 1);
+        assert_eq!(game.garbage_rows_left(), 1);
+        assert!(!game.is_game_over());
+
+        // plug the hole in the garbage row, so the next lock clears it
+        wait_for_piece(&mut game);
+        game.playfield.fill_row(0, &[]);
+        game.update(FRAME, &hard_drop());
+
+        assert_eq!(game.garbage_rows_left(), 0);
+        assert_eq!(game.game_over_reason(), Some(GameOverReason::GarbageCleared));
+    }
+    #[test]
+    fn garbage_pushes_the_active_piece_up() {
+        let mut game = started_game();
+
+        let soft_drop = GameInput { soft_drop: true, ..GameInput::default() };
+        while !is_resting(&game) {
+            game.update(FRAME, &soft_drop);
+        }
+        let resting_row = game.active_tetromino().unwrap().position().y;
+
+        game.add_garbage(2);
+
+        assert!(!game.is_game_over());
+        assert_eq!(game.active_tetromino().unwrap().position().y, resting_row + 2);
+    }
+}
@@ -0,0 +1,138 @@
+use super::playfield::PlayfieldDimensions;
+
+// the line counts a sprint can be raced over
+pub const SPRINT_LINE_GOALS: [u32; 3] = [20, 40, 100];
+pub const DEFAULT_SPRINT_LINE_GOAL: u32 = 40;
+
+// the level a marathon finishes at, unless another cap is chosen
+pub const DEFAULT_MARATHON_LEVEL_CAP: u32 = 15;
+
+// default length of an ultra game, in seconds
+pub const DEFAULT_ULTRA_TIME_LIMIT: u32 = 120;
+
+// default number of garbage rows a dig starts with. A dig always leaves at least this many empty rows above its garbage, so there's room to move the first piece
+pub const DEFAULT_DIG_GARBAGE_ROWS: u32 = 10;
+const MIN_EMPTY_ROWS_ABOVE_GARBAGE: usize = 2;
+
+// the longest ultra we allow, in seconds
+const MAX_ULTRA_TIME_LIMIT: u32 = 60 * 60;
+
+// how long the "3, 2, 1" countdown lasts before a timed game starts, in seconds
+pub const COUNTDOWN_TIME: f32 = 3.0;
+
+// The rules for when a game ends, on top of the usual topping out
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameMode {
+    // keep playing until the stack reaches the top. This is the endless variant of marathon, with no level cap
+    Endless,
+
+    // play through the levels, getting faster each time, and finish after completing level `level_cap`
+    Marathon { level_cap: u32 },
+
+    // clear `line_goal` lines as fast as possible
+    Sprint { line_goal: u32 },
+
+    // score as many points as possible in `time_limit` seconds
+    Ultra { time_limit: u32 },
+
+    // start with `garbage_rows` rows of garbage, and clear all of them as fast as possible
+    Dig { garbage_rows: u32 },
+}
+
+impl GameMode {
+    // Timed modes start with a countdown, so the player gets a fair start
+    pub fn has_countdown(&self) -> bool {
+        match *self {
+            GameMode::Endless | GameMode::Marathon { .. } => false,
+            GameMode::Sprint { .. } | GameMode::Ultra { .. } | GameMode::Dig { .. } => true,
+        }
+    }
+
+    // the number of cleared lines that finishes the game, if there is one
+    pub fn line_goal(&self) -> Option<u32> {
+        match *self {
+            GameMode::Sprint { line_goal } => Some(line_goal),
+            GameMode::Endless | GameMode::Marathon { .. } | GameMode::Ultra { .. } | GameMode::Dig { .. } => None,
+        }
+    }
+
+    // how many seconds the game lasts before it's stopped, if it has a time limit
+    pub fn time_limit(&self) -> Option<u32> {
+        match *self {
+            GameMode::Ultra { time_limit } => Some(time_limit),
+            GameMode::Endless | GameMode::Marathon { .. } | GameMode::Sprint { .. } | GameMode::Dig { .. } => None,
+        }
+    }
+
+    // the last level of the game, if it has one. Completing this level finishes the game.
+    pub fn level_cap(&self) -> Option<u32> {
+        match *self {
+            GameMode::Marathon { level_cap } => Some(level_cap),
+            GameMode::Endless | GameMode::Sprint { .. } | GameMode::Ultra { .. } | GameMode::Dig { .. } => None,
+        }
+    }
+
+    // how many rows of garbage the board starts with. Clearing all of them finishes the game.
+    pub fn starting_garbage_rows(&self) -> Option<u32> {
+        match *self {
+            GameMode::Dig { garbage_rows } => Some(garbage_rows),
+            GameMode::Endless | GameMode::Marathon { .. } | GameMode::Sprint { .. } | GameMode::Ultra { .. } => None,
+        }
+    }
+
+    // Checks the mode's parameter is one we support on a board of this size
+    pub fn validate(&self, dimensions: &PlayfieldDimensions) -> Result<(), String> {
+        match *self {
+            GameMode::Endless => Ok(()),
+            GameMode::Marathon { level_cap } => {
+                if level_cap >= 1 {
+                    Ok(())
+                }
+                else {
+                    Err("level cap must be at least 1".to_string())
+                }
+            }
+            GameMode::Sprint { line_goal } => {
+                if SPRINT_LINE_GOALS.contains(&line_goal) {
+                    Ok(())
+                }
+                else {
+                    Err(format!("sprint length must be one of {:?} lines", SPRINT_LINE_GOALS))
+                }
+            }
+            GameMode::Ultra { time_limit } => {
+                if time_limit >= 1 && time_limit <= MAX_ULTRA_TIME_LIMIT {
+                    Ok(())
+                }
+                else {
+                    Err(format!("time limit must be between 1 and {} seconds", MAX_ULTRA_TIME_LIMIT))
+                }
+            }
+            GameMode::Dig { garbage_rows } => {
+                let max_garbage_rows = dimensions.visible_height.saturating_sub(MIN_EMPTY_ROWS_ABOVE_GARBAGE) as u32;
+                if garbage_rows >= 1 && garbage_rows <= max_garbage_rows {
+                    Ok(())
+                }
+                else {
+                    Err(format!("garbage rows must be between 1 and {}", max_garbage_rows))
+                }
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            GameMode::Endless => "endless",
+            GameMode::Marathon { .. } => "marathon",
+            GameMode::Sprint { .. } => "sprint",
+            GameMode::Ultra { .. } => "ultra",
+            GameMode::Dig { .. } => "dig",
+        }
+    }
+}
+
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::Endless
+    }
+}
@@ -0,0 +1,45 @@
+use rand::Rng;
+
+// how likely each garbage row is to move its hole, unless another messiness is chosen
+pub const DEFAULT_GARBAGE_MESSINESS: f32 = 0.3;
+
+pub fn validate_messiness(messiness: f32) -> Result<(), String> {
+    if messiness >= 0.0 && messiness <= 1.0 {
+        Ok(())
+    }
+    else {
+        Err("garbage messiness must be between 0 and 1".to_string())
+    }
+}
+
+// Picks the hole column for each incoming garbage row. Messiness is the chance, from 0 to 1, that a row's hole moves to a new random column
+// instead of lining up with the row below it: 0 gives one clean well all the way down, and 1 gives a completely random hole in every row.
+pub struct GarbageGenerator<R: Rng> {
+    rng: R,
+    messiness: f32,
+    hole_column: Option<usize>,
+}
+
+impl<R: Rng> GarbageGenerator<R> {
+    pub fn new(rng: R, messiness: f32) -> Self {
+        Self {
+            rng,
+            messiness,
+            hole_column: None,
+        }
+    }
+
+    // Returns the hole column for each of the next `rows` garbage rows, from the bottom row up
+    pub fn hole_columns(&mut self, rows: usize, width: usize) -> Vec<usize> {
+        (0..rows).map(|_| {
+            let keep_hole = self.rng.gen::<f32>() >= self.messiness;
+            let hole_column = match self.hole_column {
+                Some(hole_column) if keep_hole => hole_column,
+                _ => self.rng.gen_range(0, width),
+            };
+
+            self.hole_column = Some(hole_column);
+            hole_column
+        }).collect()
+    }
+}
@@ -0,0 +1,89 @@
+use std::str::FromStr;
+use std::path::PathBuf;
+
+use super::playfield::PlayfieldDimensions;
+use super::garbage;
+use super::garbage::DEFAULT_GARBAGE_MESSINESS;
+use super::game_mode::{GameMode, DEFAULT_SPRINT_LINE_GOAL, DEFAULT_ULTRA_TIME_LIMIT, DEFAULT_MARATHON_LEVEL_CAP, DEFAULT_DIG_GARBAGE_ROWS};
+
+// Settings that come from the command line
+pub struct LaunchOptions {
+    // if set, the first game uses this seed instead of a random one, so it can be reproduced exactly
+    pub seed: Option<u64>,
+
+    // if set, play back this replay file instead of starting a new game, starting at the given frame
+    pub replay_path: Option<PathBuf>,
+    pub replay_start_frame: usize,
+
+    // size of the board and rules for new games. Replays always use the ones they were recorded with
+    pub playfield_dimensions: PlayfieldDimensions,
+    pub mode: GameMode,
+    pub garbage_messiness: f32,
+}
+
+impl LaunchOptions {
+    pub fn from_args(mut args: impl Iterator<Item=String>) -> Result<Self, String> {
+        let mut options = Self {
+            seed: None,
+            replay_path: None,
+            replay_start_frame: 0,
+            playfield_dimensions: PlayfieldDimensions::default(),
+            mode: GameMode::default(),
+            garbage_messiness: DEFAULT_GARBAGE_MESSINESS,
+        };
+        let mut mode_name = options.mode.name().to_string();
+        let mut line_goal = DEFAULT_SPRINT_LINE_GOAL;
+        let mut time_limit = DEFAULT_ULTRA_TIME_LIMIT;
+        let mut level_cap = DEFAULT_MARATHON_LEVEL_CAP;
+        let mut garbage_rows = DEFAULT_DIG_GARBAGE_ROWS;
+        let mut width = options.playfield_dimensions.width;
+        let mut visible_height = options.playfield_dimensions.visible_height;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => options.seed = Some(parse_value(&arg, args.next())?),
+                "--replay" => options.replay_path = Some(PathBuf::from(require_value(&arg, args.next())?)),
+                "--seek" => options.replay_start_frame = parse_value(&arg, args.next())?,
+                "--width" => width = parse_value(&arg, args.next())?,
+                "--height" => visible_height = parse_value(&arg, args.next())?,
+                "--mode" => mode_name = require_value(&arg, args.next())?,
+                "--lines" => line_goal = parse_value(&arg, args.next())?,
+                "--time" => time_limit = parse_value(&arg, args.next())?,
+                "--level-cap" => level_cap = parse_value(&arg, args.next())?,
+                "--messiness" => options.garbage_messiness = parse_value(&arg, args.next())?,
+                "--garbage" => garbage_rows = parse_value(&arg, args.next())?,
+                _ => return Err(format!("unrecognized argument: {}", arg)),
+            }
+        }
+
+        options.playfield_dimensions = PlayfieldDimensions::new(width, visible_height);
+        options.playfield_dimensions.validate()?;
+
+        garbage::validate_messiness(options.garbage_messiness)?;
+
+        options.mode = match mode_name.as_str() {
+            "endless" => GameMode::Endless,
+            "sprint" => GameMode::Sprint { line_goal },
+            "ultra" => GameMode::Ultra { time_limit },
+            "marathon" => GameMode::Marathon { level_cap },
+            "dig" => GameMode::Dig { garbage_rows },
+            _ => return Err(format!("unknown game mode: {}", mode_name)),
+        };
+        options.mode.validate(&options.playfield_dimensions)?;
+
+        Ok(options)
+    }
+
+    pub fn usage() -> &'static str {
+        "usage: tetris_clone [--mode endless|marathon|sprint|ultra|dig] [--level-cap <level>] [--lines 20|40|100] [--time <seconds>] [--garbage <rows>] [--messiness <0-1>] [--seed <number>] [--width <columns>] [--height <rows>] [--replay <file> [--seek <frame>]]"
+    }
+}
+
+fn parse_value<T: FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+    let value = require_value(name, value)?;
+    value.parse().map_err(|_| format!("invalid value for {}: {}", name, value))
+}
+
+fn require_value(name: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("missing value for {}", name))
+}
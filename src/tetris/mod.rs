@@ -3,4 +3,16 @@ mod tetris_input;
 mod tetromino;
 mod render;
 mod playfield;
-pub use self::app::TetrisApp;
\ No newline at end of file
+mod wall_kicks;
+mod piece_generator;
+mod next_queue;
+mod scoring;
+mod streaks;
+mod game;
+mod game_mode;
+mod garbage;
+mod launch_options;
+mod replay;
+mod replay_player;
+pub use self::app::TetrisApp;
+pub use self::launch_options::LaunchOptions;
\ No newline at end of file
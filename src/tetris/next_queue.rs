@@ -0,0 +1,36 @@
+use std::collections::VecDeque;
+
+use super::tetromino::PieceType;
+use super::piece_generator::PieceGenerator;
+
+pub const DEFAULT_NEXT_QUEUE_LENGTH: usize = 5;
+
+// the most upcoming pieces there's room to show beside the playfield
+pub const MAX_NEXT_QUEUE_LENGTH: usize = 7;
+
+// Holds the upcoming pieces so the player can see them coming. Keeps itself topped up from its piece generator.
+pub struct NextQueue {
+    generator: Box<PieceGenerator>,
+    queue: VecDeque<PieceType>,
+}
+
+impl NextQueue {
+    pub fn new(mut generator: Box<PieceGenerator>, length: usize) -> Self {
+        let queue = (0..length).map(|_| generator.next_piece()).collect();
+
+        Self {
+            generator,
+            queue,
+        }
+    }
+
+    // Removes the piece at the front of the queue and refills the back
+    pub fn pop(&mut self) -> PieceType {
+        self.queue.push_back(self.generator.next_piece());
+        self.queue.pop_front().unwrap()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&PieceType> {
+        self.queue.iter()
+    }
+}
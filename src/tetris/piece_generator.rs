@@ -0,0 +1,52 @@
+use rand::Rng;
+
+use super::tetromino::{PieceType, ALL_PIECE_TYPES};
+
+pub trait PieceGenerator {
+    fn next_piece(&mut self) -> PieceType;
+}
+
+// Deals out all seven pieces in a random order, then reshuffles and does it again. Guarantees that the player never goes more than 12 pieces without seeing any given piece.
+pub struct SevenBagGenerator<R: Rng> {
+    rng: R,
+    bag: Vec<PieceType>,
+}
+
+impl<R: Rng> SevenBagGenerator<R> {
+    pub fn new(rng: R) -> Self {
+        Self {
+            rng,
+            bag: Vec::with_capacity(ALL_PIECE_TYPES.len()),
+        }
+    }
+}
+
+impl<R: Rng> PieceGenerator for SevenBagGenerator<R> {
+    fn next_piece(&mut self) -> PieceType {
+        if self.bag.is_empty() {
+            self.bag.extend_from_slice(&ALL_PIECE_TYPES);
+            self.rng.shuffle(&mut self.bag);
+        }
+
+        self.bag.pop().unwrap()
+    }
+}
+
+// Picks every piece independently. Allows long droughts and floods of the same piece, like the very early games did.
+#[allow(unused)]
+pub struct UniformGenerator<R: Rng> {
+    rng: R,
+}
+
+impl<R: Rng> UniformGenerator<R> {
+    #[allow(unused)]
+    pub fn new(rng: R) -> Self {
+        Self { rng }
+    }
+}
+
+impl<R: Rng> PieceGenerator for UniformGenerator<R> {
+    fn next_piece(&mut self) -> PieceType {
+        *self.rng.choose(&ALL_PIECE_TYPES).unwrap()
+    }
+}
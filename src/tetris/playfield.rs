@@ -1,30 +1,127 @@
 use ndarray::{Array, Array2, Axis};
 
-use super::tetromino::{Tetromino, OrphanBlock};
+use image::Rgb;
+
+use num_traits::Zero;
+
+use super::tetromino::{Tetromino, PieceType, OrphanBlock, GARBAGE_COLOR};
+use super::wall_kicks::FINAL_KICK_INDEX;
 use ::engine::intvector::IntVector2;
 
-const PLAYFIELD_WIDTH: usize = 10;
-const PLAYFIELD_HEIGHT: usize = 22;
-const PLAYFIELD_VISIBLE_HEIGHT: usize = 20;
+// limits on custom board sizes. Cell coordinates are stored as i8, and every piece has to fit on the board
+const MIN_PLAYFIELD_WIDTH: usize = 4;
+const MAX_PLAYFIELD_WIDTH: usize = 40;
+const MIN_PLAYFIELD_VISIBLE_HEIGHT: usize = 4;
+const MAX_PLAYFIELD_VISIBLE_HEIGHT: usize = 60;
+
+// the standard board is 10 wide and 20 tall, with 2 extra rows above the top that pieces spawn into
+const DEFAULT_PLAYFIELD_WIDTH: usize = 10;
+const DEFAULT_PLAYFIELD_VISIBLE_HEIGHT: usize = 20;
+const PLAYFIELD_HIDDEN_ROWS: usize = 2;
+
+// Size of the board, in cells. `height` includes the hidden rows above `visible_height`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PlayfieldDimensions {
+    pub width: usize,
+    pub height: usize,
+    pub visible_height: usize,
+}
+
+impl PlayfieldDimensions {
+    pub fn new(width: usize, visible_height: usize) -> Self {
+        Self {
+            width,
+            height: visible_height + PLAYFIELD_HIDDEN_ROWS,
+            visible_height,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.width < MIN_PLAYFIELD_WIDTH || self.width > MAX_PLAYFIELD_WIDTH {
+            return Err(format!("playfield width must be between {} and {}", MIN_PLAYFIELD_WIDTH, MAX_PLAYFIELD_WIDTH));
+        }
+        if self.visible_height < MIN_PLAYFIELD_VISIBLE_HEIGHT || self.visible_height > MAX_PLAYFIELD_VISIBLE_HEIGHT {
+            return Err(format!("playfield height must be between {} and {}", MIN_PLAYFIELD_VISIBLE_HEIGHT, MAX_PLAYFIELD_VISIBLE_HEIGHT));
+        }
+        if self.height != self.visible_height + PLAYFIELD_HIDDEN_ROWS {
+            return Err(format!("playfield must have {} hidden rows", PLAYFIELD_HIDDEN_ROWS));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for PlayfieldDimensions {
+    fn default() -> Self {
+        Self::new(DEFAULT_PLAYFIELD_WIDTH, DEFAULT_PLAYFIELD_VISIBLE_HEIGHT)
+    }
+}
 
 pub struct Playfield {
     cells: Array2<Option<OrphanBlock>>,
+    visible_height: usize,
+
+    // for each row, whether it's garbage that the player has to dig through to finish the game. Moves along with its row as the board shifts.
+    tracked_garbage: Vec<bool>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TSpin {
+    None,
+    Mini,
+    Full,
+}
+
+pub struct LockResult {
+    // indexes of the rows that were cleared by this lock, from bottom to top, relative to the board before collapsing
+    pub cleared_rows: Vec<usize>,
+    pub t_spin: TSpin,
+}
+
+impl LockResult {
+    pub fn lines_cleared(&self) -> usize {
+        self.cleared_rows.len()
+    }
 }
 
 impl Playfield {
-    pub fn new_empty() -> Self {
+    pub fn new_empty(dimensions: PlayfieldDimensions) -> Self {
         Self {
-            cells: Array::default((PLAYFIELD_WIDTH,PLAYFIELD_HEIGHT)),
+            cells: Array::default((dimensions.width, dimensions.height)),
+            visible_height: dimensions.visible_height,
+            tracked_garbage: vec![false; dimensions.height],
         }
     }
 
-    pub fn spawn_location(&self) -> IntVector2<i8> {
-        IntVector2::new(PLAYFIELD_WIDTH as i8 / 2, PLAYFIELD_HEIGHT as i8 - 2)
+    pub fn dimensions(&self) -> PlayfieldDimensions {
+        PlayfieldDimensions {
+            width: self.cells.len_of(Axis(0)),
+            height: self.cells.len_of(Axis(1)),
+            visible_height: self.visible_height,
+        }
+    }
+
+    // Pieces spawn just left of center, so that the 4-wide I piece fits on boards of any width.
+    // Following the guideline, each piece spawns in its starting orientation with its lowest blocks in the first hidden row, just above the visible area.
+    pub fn spawn_location(&self, piece_type: PieceType) -> IntVector2<i8> {
+        let dimensions = self.dimensions();
+
+        let lowest_offset = Tetromino::new(piece_type, IntVector2::zero()).get_occupied_cells().iter().map(|cell| cell.y).min().unwrap_or(0);
+        IntVector2::new((dimensions.width as i8 - 1) / 2, dimensions.visible_height as i8 - lowest_offset)
+    }
+
+    // how many of the rows pushed in with `tracked` set are still on the board
+    pub fn tracked_garbage_rows(&self) -> usize {
+        self.tracked_garbage.iter().filter(|&&tracked| tracked).count()
+    }
+
+    pub fn is_above_visible_area(&self, tetromino: &Tetromino) -> bool {
+        tetromino.get_occupied_cells().iter().all(|cell| cell.y >= self.visible_height as i8)
     }
 
-    #[allow(unused)]
     pub fn visible_dimensions(&self) -> (usize, usize) {
-        (PLAYFIELD_WIDTH, PLAYFIELD_VISIBLE_HEIGHT)
+        let dimensions = self.dimensions();
+        (dimensions.width, dimensions.visible_height)
     }
 
     pub fn is_valid_placement(&self, tetromino: &Tetromino) -> bool {
@@ -52,13 +149,152 @@ impl Playfield {
         true
     }
 
-    pub fn lock_tetromino(&mut self, tetromino: &Tetromino) {
+    // Returns a copy of the tetromino moved as far down as it can go without colliding with anything
+    pub fn hard_drop_position(&self, tetromino: &Tetromino) -> Tetromino {
+        let mut dropped = *tetromino;
+        loop {
+            let moved = dropped.moved(IntVector2::new(0, -1));
+            if self.is_valid_placement(&moved) {
+                dropped = moved;
+            }
+            else {
+                return dropped;
+            }
+        }
+    }
+
+    // Writes the tetromino into the playfield and clears any rows it completes.
+    // `rotation_kick` is the kick index of the tetromino's last successful move if that move was a rotation, or None otherwise. It's used to detect T-spins.
+    pub fn lock_tetromino(&mut self, tetromino: &Tetromino, rotation_kick: Option<usize>) -> LockResult {
+        // T-spins depend on the blocks around the piece, so detect them before we write the piece in
+        let t_spin = self.detect_t_spin(tetromino, rotation_kick);
+
         let color = tetromino.get_color();
 
         for cell in &tetromino.get_occupied_cells() {
             let coordinates = [cell.x as usize, cell.y as usize];
             self.cells[coordinates] = Some(OrphanBlock { color });
         }
+
+        let cleared_rows = self.clear_full_rows();
+        LockResult { cleared_rows, t_spin }
+    }
+
+    // Pushes every row up by one row per entry in `hole_columns`, and fills the space at the bottom with garbage rows.
+    // Each garbage row is full except for one empty cell, at the matching entry of `hole_columns`, from the bottom row up.
+    // If `tracked` is set, the new rows count towards `tracked_garbage_rows` until they're cleared.
+    // Returns true if any blocks were pushed off the top of the playfield.
+    pub fn push_garbage(&mut self, hole_columns: &[usize], tracked: bool) -> bool {
+        let width = self.cells.len_of(Axis(0));
+        let height = self.cells.len_of(Axis(1));
+        let garbage_rows = hole_columns.len().min(height);
+
+        let topped_out = (height - garbage_rows..height).any(|row| self.cells.subview(Axis(1), row).iter().any(|cell| cell.is_some()));
+
+        // walk down the board, so that each row moves up before the row below it overwrites it
+        for row in (garbage_rows..height).rev() {
+            for x in 0..width {
+                self.cells[[x, row]] = self.cells[[x, row - garbage_rows]].take();
+            }
+            self.tracked_garbage[row] = self.tracked_garbage[row - garbage_rows];
+        }
+
+        for (row, &hole_column) in hole_columns.iter().take(garbage_rows).enumerate() {
+            self.tracked_garbage[row] = tracked;
+            for x in 0..width {
+                self.cells[[x, row]] = if x == hole_column { None } else { Some(OrphanBlock { color: Rgb(GARBAGE_COLOR) }) };
+            }
+        }
+
+        topped_out
+    }
+
+    // Uses the 3-corner rule: a T piece that got into place by rotating is a T-spin if at least 3 of the 4 cells diagonal to its center are occupied.
+    // It's a full T-spin if both corners on the side the T points towards are occupied, and a mini T-spin otherwise -- unless the rotation used the final SRS kick, which always counts as full.
+    fn detect_t_spin(&self, tetromino: &Tetromino, rotation_kick: Option<usize>) -> TSpin {
+        if tetromino.piece_type() != PieceType::TBlock {
+            return TSpin::None;
+        }
+
+        let kick_index = match rotation_kick {
+            Some(kick_index) => kick_index,
+            None => return TSpin::None,
+        };
+
+        // the T's stem starts out pointing up, so rotate "up" to find the direction it's pointing now
+        let center = tetromino.position();
+        let facing = IntVector2::new(0, 1).rotate_around_cell(IntVector2::zero(), tetromino.orientation());
+        let side = IntVector2::new(facing.y, -facing.x);
+
+        let front_corners = [center + facing + side, center + facing - side];
+        let back_corners = [center - facing + side, center - facing - side];
+
+        let occupied_front = front_corners.iter().filter(|&&corner| self.is_occupied(corner)).count();
+        let occupied_back = back_corners.iter().filter(|&&corner| self.is_occupied(corner)).count();
+
+        if occupied_front + occupied_back < 3 {
+            TSpin::None
+        }
+        else if occupied_front == 2 || kick_index == FINAL_KICK_INDEX {
+            TSpin::Full
+        }
+        else {
+            TSpin::Mini
+        }
+    }
+
+    // Cells outside the playfield count as occupied, since pieces can't go there
+    fn is_occupied(&self, cell: IntVector2<i8>) -> bool {
+        if cell.x < 0 || cell.y < 0 {
+            return true;
+        }
+
+        match self.cells.get([cell.x as usize, cell.y as usize]) {
+            Some(&Some(_)) => true,
+            Some(&None) => false,
+            None => true,
+        }
+    }
+
+    fn is_row_full(&self, row: usize) -> bool {
+        self.cells.subview(Axis(1), row).iter().all(|cell| cell.is_some())
+    }
+
+    // Removes every full row, and collapses the rows above them down to fill the gaps. Returns the indexes of the removed rows.
+    fn clear_full_rows(&mut self) -> Vec<usize> {
+        let width = self.cells.len_of(Axis(0));
+        let height = self.cells.len_of(Axis(1));
+
+        let cleared_rows: Vec<usize> = (0..height).filter(|&row| self.is_row_full(row)).collect();
+        if cleared_rows.is_empty() {
+            return cleared_rows;
+        }
+
+        // walk up the board, moving each surviving row down to the lowest row that hasn't been filled yet
+        let mut destination_row = 0;
+        for source_row in 0..height {
+            if self.is_row_full(source_row) {
+                continue;
+            }
+
+            if source_row != destination_row {
+                for x in 0..width {
+                    self.cells[[x, destination_row]] = self.cells[[x, source_row]].take();
+                }
+                self.tracked_garbage[destination_row] = self.tracked_garbage[source_row];
+            }
+            destination_row += 1;
+        }
+
+        // everything above the last surviving row is now empty
+        for row in destination_row..height {
+            self.tracked_garbage[row] = false;
+            for x in 0..width {
+                self.cells[[x, row]] = None;
+            }
+        }
+
+        cleared_rows
     }
 
     pub fn iter_orphans(&self) -> impl Iterator<Item=(IntVector2<i8>, &OrphanBlock)> {
@@ -71,4 +307,163 @@ impl Playfield {
             }
         })
     }
-}
\ No newline at end of file
+}
+
+// lets tests elsewhere in the crate build up a board without playing pieces onto it
+#[cfg(test)]
+impl Playfield {
+    // fills a whole row with blocks, except for the given columns
+    pub fn fill_row(&mut self, row: usize, holes: &[usize]) {
+        for x in 0..self.cells.len_of(Axis(0)) {
+            if !holes.contains(&x) {
+                self.cells[[x, row]] = Some(OrphanBlock { color: Rgb([255, 255, 255]) });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use ::engine::intvector::IntVector2;
+    use super::{Playfield, PlayfieldDimensions, TSpin};
+    use super::super::tetromino::{Tetromino, PieceType, OrphanBlock};
+    use super::super::wall_kicks::FINAL_KICK_INDEX;
+
+    fn empty_playfield() -> Playfield {
+        Playfield::new_empty(PlayfieldDimensions::default())
+    }
+
+    fn place_block(playfield: &mut Playfield, x: usize, y: usize) {
+        playfield.cells[[x, y]] = Some(OrphanBlock { color: Rgb([255, 255, 255]) });
+    }
+
+    fn is_filled(playfield: &Playfield, x: usize, y: usize) -> bool {
+        playfield.cells[[x, y]].is_some()
+    }
+
+    // a T piece centered on (x, y), turned clockwise `turns` times from its spawn orientation
+    fn t_piece(x: i8, y: i8, turns: usize) -> Tetromino {
+        (0..turns).fold(Tetromino::new(PieceType::TBlock, IntVector2::new(x, y)), |tetromino, _| tetromino.rotated_right())
+    }
+
+    // A slot that's one wide on the bottom row and three wide on the row above, with a block overhanging its left side.
+    // A T pointing down fills it for a T-spin double. A T pointing up sits in the top of it with only one of its front corners filled, for a mini.
+    fn t_slot_setup() -> Playfield {
+        let mut playfield = empty_playfield();
+        playfield.fill_row(0, &[4]);
+        playfield.fill_row(1, &[3, 4, 5]);
+        place_block(&mut playfield, 3, 2);
+        playfield
+    }
+
+    #[test]
+    fn clearing_a_row_collapses_the_rows_above() {
+        let mut playfield = empty_playfield();
+        playfield.fill_row(0, &[0, 1, 2, 3]);
+        place_block(&mut playfield, 9, 1);
+
+        let result = playfield.lock_tetromino(&Tetromino::new(PieceType::IBlock, IntVector2::new(1, 0)), None);
+
+        assert_eq!(result.cleared_rows, vec![0]);
+        assert_eq!(result.t_spin, TSpin::None);
+        assert!(is_filled(&playfield, 9, 0));
+        assert!(!is_filled(&playfield, 9, 1));
+        assert_eq!(playfield.iter_orphans().count(), 1);
+    }
+
+    #[test]
+    fn locking_without_completing_a_row_clears_nothing() {
+        let mut playfield = empty_playfield();
+        playfield.fill_row(0, &[0, 1, 2, 3, 4]);
+
+        let result = playfield.lock_tetromino(&Tetromino::new(PieceType::IBlock, IntVector2::new(1, 0)), None);
+
+        assert!(result.cleared_rows.is_empty());
+        assert_eq!(playfield.iter_orphans().count(), 9);
+    }
+
+    #[test]
+    fn rotated_t_into_slot_is_a_t_spin_double() {
+        let mut playfield = t_slot_setup();
+
+        let result = playfield.lock_tetromino(&t_piece(4, 1, 2), Some(0));
+
+        assert_eq!(result.t_spin, TSpin::Full);
+        assert_eq!(result.cleared_rows, vec![0, 1]);
+
+        // the overhanging block falls down to the bottom row
+        assert!(is_filled(&playfield, 3, 0));
+        assert_eq!(playfield.iter_orphans().count(), 1);
+    }
+
+    #[test]
+    fn t_that_didnt_rotate_into_place_is_not_a_t_spin() {
+        let mut playfield = t_slot_setup();
+
+        let result = playfield.lock_tetromino(&t_piece(4, 1, 2), None);
+
+        assert_eq!(result.t_spin, TSpin::None);
+        assert_eq!(result.lines_cleared(), 2);
+    }
+
+    #[test]
+    fn t_with_one_front_corner_filled_is_a_mini_t_spin() {
+        let mut playfield = t_slot_setup();
+
+        let result = playfield.lock_tetromino(&t_piece(4, 1, 0), Some(0));
+
+        assert_eq!(result.t_spin, TSpin::Mini);
+        assert_eq!(result.cleared_rows, vec![1]);
+    }
+
+    #[test]
+    fn final_kick_upgrades_a_mini_t_spin_to_full() {
+        let mut playfield = t_slot_setup();
+
+        let result = playfield.lock_tetromino(&t_piece(4, 1, 0), Some(FINAL_KICK_INDEX));
+
+        assert_eq!(result.t_spin, TSpin::Full);
+    }
+    #[test]
+    fn garbage_pushes_rows_up_along_with_their_garbage_flags() {
+        let mut playfield = empty_playfield();
+        playfield.push_garbage(&[3], true);
+        place_block(&mut playfield, 9, 1);
+
+        let topped_out = playfield.push_garbage(&[1, 2], false);
+
+        assert!(!topped_out);
+        assert_eq!(&playfield.tracked_garbage[..4], &[false, false, true, false]);
+        assert_eq!(playfield.tracked_garbage_rows(), 1);
+
+        // the tracked row and the block above it both moved up two rows, hole and all
+        assert!(!is_filled(&playfield, 3, 2));
+        assert!(is_filled(&playfield, 9, 3));
+        assert!(!is_filled(&playfield, 9, 1));
+    }
+
+    #[test]
+    fn each_garbage_row_has_exactly_one_hole() {
+        let mut playfield = empty_playfield();
+        let hole_columns = [2, 7, 0];
+
+        playfield.push_garbage(&hole_columns, false);
+
+        for (row, &hole_column) in hole_columns.iter().enumerate() {
+            let empty_columns: Vec<usize> = (0..playfield.dimensions().width).filter(|&x| !is_filled(&playfield, x, row)).collect();
+            assert_eq!(empty_columns, vec![hole_column]);
+        }
+        assert_eq!(playfield.iter_orphans().count(), hole_columns.len() * (playfield.dimensions().width - 1));
+    }
+
+    #[test]
+    fn garbage_that_pushes_blocks_off_the_top_reports_a_top_out() {
+        let mut playfield = empty_playfield();
+        let top_row = playfield.dimensions().height - 1;
+        place_block(&mut playfield, 0, top_row);
+
+        assert!(playfield.push_garbage(&[0], false));
+    }
+}
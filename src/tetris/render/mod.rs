@@ -1,9 +1,10 @@
 use piston_window::PistonWindow;
 use gfx_device_gl::Resources;
-use cgmath::Matrix4;
-use image::Rgb;
+use cgmath::{Matrix4, Vector3};
+use image::Rgba;
 
 mod tetris_block;
+mod block_font;
 
 pub struct RenderState {
     tetris_block_data: tetris_block::TetrisBlock<Resources>,
@@ -16,7 +17,43 @@ impl RenderState {
         }
     }
 
-    pub fn render_tetris_block(&self, window: &mut PistonWindow, transform: &Matrix4<f32>, tint_color: &Rgb<u8>) {
+    pub fn render_tetris_block(&self, window: &mut PistonWindow, transform: &Matrix4<f32>, tint_color: &Rgba<u8>) {
         self.tetris_block_data.render(&mut window.encoder, transform, tint_color);
     }
-}
\ No newline at end of file
+
+    // Draws a single line of text, one tetris block per font pixel. The transform places the center of the text's top left pixel, and each pixel is one unit wide.
+    pub fn render_text(&self, window: &mut PistonWindow, transform: &Matrix4<f32>, text: &str, tint_color: &Rgba<u8>) {
+        for (character_index, character) in text.chars().enumerate() {
+            let glyph = block_font::glyph(character);
+
+            for (row_index, &row) in glyph.iter().enumerate() {
+                for column_index in 0..block_font::GLYPH_WIDTH {
+                    if row & (1u8 << (block_font::GLYPH_WIDTH - 1 - column_index)) == 0 {
+                        continue;
+                    }
+
+                    let pixel_offset = Vector3::new((character_index * block_font::GLYPH_ADVANCE + column_index) as f32, -(row_index as f32), 0.0);
+                    let pixel_transform = *transform * Matrix4::from_translation(pixel_offset);
+
+                    self.tetris_block_data.render(&mut window.encoder, &pixel_transform, tint_color);
+                }
+            }
+        }
+    }
+
+    // the distance between the centers of the leftmost and rightmost pixel columns of this text, in font pixels
+    pub fn text_width(text: &str) -> f32 {
+        let character_count = text.chars().count();
+        if character_count == 0 {
+            0.0
+        }
+        else {
+            (character_count * block_font::GLYPH_ADVANCE - 2) as f32
+        }
+    }
+
+    // the distance from the top of one line of text to the top of the next, in font pixels
+    pub fn line_height() -> f32 {
+        (block_font::GLYPH_HEIGHT + 2) as f32
+    }
+}
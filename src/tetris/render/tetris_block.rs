@@ -8,7 +8,7 @@ use gfx::traits::FactoryExt;
 use gfx::handle::RenderTargetView;
 use cgmath::Matrix4;
 use piston_window::{Texture, TextureSettings, Flip, Filter};
-use image::Rgb;
+use image::Rgba;
 
 gfx_defines!{
     vertex Vertex {
@@ -29,7 +29,7 @@ gfx_defines!{
         vbuf: gfx::VertexBuffer<Vertex> = (),
         transients: gfx::ConstantBuffer<Transients> = "Transients",
         texture_albedo: gfx::TextureSampler<[f32; 4]> = "t_albedoMap",
-        out: gfx::RenderTarget<Srgba8> = "Target0",
+        out: gfx::BlendTarget<Srgba8> = ("Target0", gfx::state::MASK_ALL, gfx::preset::blend::ALPHA),
     }
 }
 
@@ -86,9 +86,9 @@ impl<R: gfx::Resources> TetrisBlock<R> {
         }
     }
 
-    pub fn render(&self, encoder: &mut gfx::Encoder<R, impl gfx::CommandBuffer<R>>, transform: &Matrix4<f32>, tint_color: &Rgb<u8>) {
+    pub fn render(&self, encoder: &mut gfx::Encoder<R, impl gfx::CommandBuffer<R>>, transform: &Matrix4<f32>, tint_color: &Rgba<u8>) {
 
-        let tint_color_vec = [tint_color[0] as f32 / 255.0, tint_color[1] as f32 / 255.0, tint_color[2] as f32 / 255.0, 1.0];
+        let tint_color_vec = [tint_color[0] as f32 / 255.0, tint_color[1] as f32 / 255.0, tint_color[2] as f32 / 255.0, tint_color[3] as f32 / 255.0];
         let transient_data = Transients {
             transform: (*transform).into(), 
             tint_color: tint_color_vec,
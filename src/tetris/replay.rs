@@ -0,0 +1,386 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::game::{GameConfig, GameInput};
+use super::playfield::PlayfieldDimensions;
+use super::game_mode::GameMode;
+
+// every replay file starts with these bytes, followed by the format version
+const REPLAY_MAGIC: &[u8; 4] = b"TTRP";
+pub const REPLAY_FORMAT_VERSION: u16 = 7;
+
+// the longest replay we'll load: four hours at 30 updates per second. Every run in the file expands to up to 65535 frames,
+// so without a limit a tiny hand-made file could ask for gigabytes of frames
+const MAX_REPLAY_FRAMES: usize = 30 * 60 * 60 * 4;
+
+const REPLAY_DIRECTORY: &str = "replays";
+const REPLAY_EXTENSION: &str = "ttr";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ReplayFrame {
+    pub dt: f32,
+    pub input: GameInput,
+}
+
+// Everything needed to reproduce a game exactly: the seed, the rules, and the logical inputs for every frame.
+pub struct Replay {
+    pub game_version: String,
+    pub seed: u64,
+    pub config: GameConfig,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, config: GameConfig) -> Self {
+        Self {
+            game_version: env!("CARGO_PKG_VERSION").to_string(),
+            seed,
+            config,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn record_frame(&mut self, dt: f32, input: GameInput) {
+        self.frames.push(ReplayFrame { dt, input });
+    }
+
+    // Writes the replay into the replays directory, with a name based on the current time and the seed. Returns the path of the new file.
+    pub fn save(&self) -> io::Result<PathBuf> {
+        fs::create_dir_all(REPLAY_DIRECTORY)?;
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0);
+        let mut path = Path::new(REPLAY_DIRECTORY).join(format!("{}-{}", timestamp, self.seed));
+        path.set_extension(REPLAY_EXTENSION);
+
+        let mut writer = BufWriter::new(File::create(&path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+
+        Ok(path)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        Self::read_from(&mut reader)
+    }
+
+    // The format is a small header, then the frames, run-length encoded. At a fixed update rate nearly every frame has the same dt, and inputs change rarely, so the runs get long.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(REPLAY_MAGIC)?;
+        write_u16(writer, REPLAY_FORMAT_VERSION)?;
+
+        write_u16(writer, self.game_version.len() as u16)?;
+        writer.write_all(self.game_version.as_bytes())?;
+
+        write_u64(writer, self.seed)?;
+        write_config(writer, &self.config)?;
+
+        let runs = self.frame_runs();
+        write_u32(writer, runs.len() as u32)?;
+        for &(run_length, frame) in runs.iter() {
+            write_u16(writer, run_length)?;
+            write_f32(writer, frame.dt)?;
+            writer.write_all(&[frame.input.to_bits()])?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != REPLAY_MAGIC {
+            return Err(invalid_data("not a replay file".to_string()));
+        }
+
+        let format_version = read_u16(reader)?;
+        if format_version != REPLAY_FORMAT_VERSION {
+            return Err(invalid_data(format!("unsupported replay format version {}", format_version)));
+        }
+
+        let mut game_version = vec![0; read_u16(reader)? as usize];
+        reader.read_exact(&mut game_version)?;
+        let game_version = String::from_utf8(game_version).map_err(|_| invalid_data("invalid game version".to_string()))?;
+
+        let seed = read_u64(reader)?;
+        let config = read_config(reader)?;
+        config.validate().map_err(invalid_data)?;
+
+        let mut frames = Vec::new();
+        for _ in 0..read_u32(reader)? {
+            let run_length = read_u16(reader)?;
+            let dt = read_f32(reader)?;
+            let input = GameInput::from_bits(read_u8(reader)?);
+
+            if frames.len() + run_length as usize > MAX_REPLAY_FRAMES {
+                return Err(invalid_data(format!("replay is longer than {} frames", MAX_REPLAY_FRAMES)));
+            }
+
+            for _ in 0..run_length {
+                frames.push(ReplayFrame { dt, input });
+            }
+        }
+
+        Ok(Self {
+            game_version,
+            seed,
+            config,
+            frames,
+        })
+    }
+
+    // groups consecutive identical frames into (count, frame) pairs
+    fn frame_runs(&self) -> Vec<(u16, ReplayFrame)> {
+        let mut runs: Vec<(u16, ReplayFrame)> = Vec::new();
+
+        for &frame in self.frames.iter() {
+            if let Some(last_run) = runs.last_mut() {
+                if last_run.1 == frame && last_run.0 < u16::max_value() {
+                    last_run.0 += 1;
+                    continue;
+                }
+            }
+
+            runs.push((1, frame));
+        }
+
+        runs
+    }
+}
+
+fn write_config(writer: &mut impl Write, config: &GameConfig) -> io::Result<()> {
+    write_mode(writer, config.mode)?;
+    write_u32(writer, config.playfield_dimensions.width as u32)?;
+    write_u32(writer, config.playfield_dimensions.height as u32)?;
+    write_u32(writer, config.playfield_dimensions.visible_height as u32)?;
+    write_u32(writer, config.next_queue_length as u32)?;
+    write_u32(writer, config.starting_level)?;
+    write_u32(writer, config.das_millis)?;
+    write_u32(writer, config.arr_millis)?;
+    write_u32(writer, config.soft_drop_millis)?;
+    write_u32(writer, config.max_lock_resets)?;
+    write_f32(writer, config.garbage_messiness)
+}
+
+fn read_config(reader: &mut impl Read) -> io::Result<GameConfig> {
+    Ok(GameConfig {
+        mode: read_mode(reader)?,
+        playfield_dimensions: PlayfieldDimensions {
+            width: read_u32(reader)? as usize,
+            height: read_u32(reader)? as usize,
+            visible_height: read_u32(reader)? as usize,
+        },
+        next_queue_length: read_u32(reader)? as usize,
+        starting_level: read_u32(reader)?,
+        das_millis: read_u32(reader)?,
+        arr_millis: read_u32(reader)?,
+        soft_drop_millis: read_u32(reader)?,
+        max_lock_resets: read_u32(reader)?,
+        garbage_messiness: read_f32(reader)?,
+    })
+}
+
+// each mode is stored as a one byte tag, followed by its parameter
+fn write_mode(writer: &mut impl Write, mode: GameMode) -> io::Result<()> {
+    let (tag, parameter) = match mode {
+        GameMode::Endless => (0, 0),
+        GameMode::Sprint { line_goal } => (1, line_goal),
+        GameMode::Ultra { time_limit } => (2, time_limit),
+        GameMode::Marathon { level_cap } => (3, level_cap),
+        GameMode::Dig { garbage_rows } => (4, garbage_rows),
+    };
+
+    writer.write_all(&[tag])?;
+    write_u32(writer, parameter)
+}
+
+fn read_mode(reader: &mut impl Read) -> io::Result<GameMode> {
+    let tag = read_u8(reader)?;
+    let parameter = read_u32(reader)?;
+
+    match tag {
+        0 => Ok(GameMode::Endless),
+        1 => Ok(GameMode::Sprint { line_goal: parameter }),
+        2 => Ok(GameMode::Ultra { time_limit: parameter }),
+        3 => Ok(GameMode::Marathon { level_cap: parameter }),
+        4 => Ok(GameMode::Dig { garbage_rows: parameter }),
+        _ => Err(invalid_data(format!("unknown game mode {}", tag))),
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// all numbers are stored little-endian
+fn write_u16(writer: &mut impl Write, value: u16) -> io::Result<()> {
+    writer.write_all(&[value as u8, (value >> 8) as u8])
+}
+
+fn write_u32(writer: &mut impl Write, value: u32) -> io::Result<()> {
+    write_u16(writer, value as u16)?;
+    write_u16(writer, (value >> 16) as u16)
+}
+
+fn write_u64(writer: &mut impl Write, value: u64) -> io::Result<()> {
+    write_u32(writer, value as u32)?;
+    write_u32(writer, (value >> 32) as u32)
+}
+
+fn write_f32(writer: &mut impl Write, value: f32) -> io::Result<()> {
+    write_u32(writer, value.to_bits())
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u16(reader: &mut impl Read) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0] as u16 | (bytes[1] as u16) << 8)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let low = read_u16(reader)? as u32;
+    let high = read_u16(reader)? as u32;
+    Ok(low | high << 16)
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let low = read_u32(reader)? as u64;
+    let high = read_u32(reader)? as u64;
+    Ok(low | high << 32)
+}
+
+fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    Ok(f32::from_bits(read_u32(reader)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{Replay, REPLAY_FORMAT_VERSION, MAX_REPLAY_FRAMES};
+    use super::super::game::{GameConfig, GameInput};
+
+    const FRAME: f32 = 1.0 / 30.0;
+
+    fn sample_replay() -> Replay {
+        let mut replay = Replay::new(42, GameConfig::default());
+        let left = GameInput { left: true, ..GameInput::default() };
+
+        for frame in 0..100 {
+            replay.record_frame(FRAME, if frame % 10 < 3 { left } else { GameInput::default() });
+        }
+
+        replay
+    }
+
+    fn to_bytes(replay: &Replay) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        replay.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn read_error(bytes: &[u8]) -> io::Error {
+        match Replay::read_from(&mut &bytes[..]) {
+            Ok(_) => panic!("replay should have been rejected"),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn replay_survives_a_round_trip() {
+        let replay = sample_replay();
+
+        let loaded = Replay::read_from(&mut &to_bytes(&replay)[..]).unwrap();
+
+        assert_eq!(loaded.game_version, replay.game_version);
+        assert_eq!(loaded.seed, replay.seed);
+        assert_eq!(loaded.config, replay.config);
+        assert_eq!(loaded.frames, replay.frames);
+    }
+
+    #[test]
+    fn long_runs_are_split_at_the_counter_limit() {
+        let mut replay = Replay::new(7, GameConfig::default());
+        let frame_count = u16::max_value() as usize + 5;
+        for _ in 0..frame_count {
+            replay.record_frame(FRAME, GameInput::default());
+        }
+
+        let run_lengths: Vec<u16> = replay.frame_runs().iter().map(|&(run_length, _)| run_length).collect();
+        assert_eq!(run_lengths, vec![u16::max_value(), 5]);
+
+        let loaded = Replay::read_from(&mut &to_bytes(&replay)[..]).unwrap();
+        assert_eq!(loaded.frames.len(), frame_count);
+    }
+
+    #[test]
+    fn rejects_replays_that_are_too_long() {
+        let mut replay = Replay::new(7, GameConfig::default());
+        for _ in 0..MAX_REPLAY_FRAMES + 1 {
+            replay.record_frame(FRAME, GameInput::default());
+        }
+
+        assert_eq!(read_error(&to_bytes(&replay)).kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_replays() {
+        let mut bytes = to_bytes(&sample_replay());
+        bytes[0] = b'X';
+
+        assert_eq!(read_error(&bytes).kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_other_format_versions() {
+        let mut bytes = to_bytes(&sample_replay());
+
+        // the version comes straight after the magic bytes, little-endian
+        let other_version = REPLAY_FORMAT_VERSION + 1;
+        bytes[4] = other_version as u8;
+        bytes[5] = (other_version >> 8) as u8;
+
+        assert_eq!(read_error(&bytes).kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_settings_out_of_range() {
+        let config = GameConfig { next_queue_length: 1000000, ..GameConfig::default() };
+        let bytes = to_bytes(&Replay::new(42, config));
+
+        assert_eq!(read_error(&bytes).kind(), io::ErrorKind::InvalidData);
+    }
+
+    fn assert_config_rejected(config: GameConfig) {
+        let bytes = to_bytes(&Replay::new(42, config));
+        assert_eq!(read_error(&bytes).kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_das_out_of_range() {
+        assert_config_rejected(GameConfig { das_millis: 1000000, ..GameConfig::default() });
+    }
+
+    #[test]
+    fn rejects_arr_out_of_range() {
+        assert_config_rejected(GameConfig { arr_millis: 1000000, ..GameConfig::default() });
+    }
+
+    #[test]
+    fn rejects_soft_drop_interval_out_of_range() {
+        assert_config_rejected(GameConfig { soft_drop_millis: 1000000, ..GameConfig::default() });
+    }
+
+    #[test]
+    fn rejects_too_many_lock_resets() {
+        assert_config_rejected(GameConfig { max_lock_resets: u32::max_value(), ..GameConfig::default() });
+    }
+}
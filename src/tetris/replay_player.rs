@@ -0,0 +1,206 @@
+use super::replay::{Replay, ReplayFrame};
+
+// Steps through a recorded replay, with controls for pausing, changing speed, and seeking
+pub struct ReplayPlayer {
+    replay: Replay,
+
+    // index of the next frame to hand to the game
+    position: usize,
+
+    paused: bool,
+    speed: f32,
+
+    // fractional frames carried over between updates, so that slow motion advances one frame every few updates
+    pending_frames: f32,
+
+    pending_seek: Option<usize>,
+}
+
+// The frames the game should play through during this update. If `restart` is true, the game has to be rebuilt from the replay's seed and config before playing them.
+pub struct InputFrames {
+    pub restart: bool,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            position: 0,
+            paused: false,
+            speed: 1.0,
+            pending_frames: 0.0,
+            pending_seek: None,
+        }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.replay.frames.len()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn toggle_paused(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    // 1.0 is normal speed, higher values fast forward, lower values play in slow motion
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    // Jumps to the given frame the next time the player advances. Seeking backwards restarts the game and fast-forwards through everything before the target.
+    pub fn seek(&mut self, frame: usize) {
+        self.pending_seek = Some(frame.min(self.len()));
+    }
+
+    // the target of the most recent seek, or the current position if there's no seek waiting
+    pub fn seek_target(&self) -> usize {
+        self.pending_seek.unwrap_or(self.position)
+    }
+
+    // Returns the recorded frames the game should play through during this update, based on the playback speed
+    pub fn advance(&mut self) -> InputFrames {
+        if let Some(target) = self.pending_seek.take() {
+            let restart = target < self.position;
+            let start = if restart { 0 } else { self.position };
+
+            self.position = target;
+            self.pending_frames = 0.0;
+
+            return InputFrames {
+                restart,
+                frames: self.replay.frames[start..target].to_vec(),
+            };
+        }
+
+        if self.paused {
+            return InputFrames { restart: false, frames: Vec::new() };
+        }
+
+        self.pending_frames += self.speed;
+        let frame_count = (self.pending_frames as usize).min(self.len() - self.position);
+        self.pending_frames -= self.pending_frames.floor();
+
+        let start = self.position;
+        self.position += frame_count;
+
+        InputFrames {
+            restart: false,
+            frames: self.replay.frames[start..self.position].to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReplayPlayer, InputFrames};
+    use super::super::replay::Replay;
+    use super::super::game::{GameConfig, GameInput};
+
+    // a player for a ten frame replay, where each frame's dt is its own index so the frames can be told apart
+    fn numbered_player() -> ReplayPlayer {
+        let mut replay = Replay::new(42, GameConfig::default());
+        for index in 0..10 {
+            replay.record_frame(index as f32, GameInput::default());
+        }
+
+        ReplayPlayer::new(replay)
+    }
+
+    fn frame_numbers(input_frames: &InputFrames) -> Vec<usize> {
+        input_frames.frames.iter().map(|frame| frame.dt as usize).collect()
+    }
+
+    #[test]
+    fn plays_one_frame_per_update_at_normal_speed() {
+        let mut player = numbered_player();
+
+        for expected in 0..10 {
+            let input_frames = player.advance();
+            assert!(!input_frames.restart);
+            assert_eq!(frame_numbers(&input_frames), vec![expected]);
+        }
+
+        // once the replay runs out, nothing more is played
+        assert!(player.advance().frames.is_empty());
+        assert_eq!(player.position(), 10);
+    }
+
+    #[test]
+    fn seeking_forwards_plays_the_skipped_frames() {
+        let mut player = numbered_player();
+        player.advance();
+
+        player.seek(4);
+        let input_frames = player.advance();
+
+        assert!(!input_frames.restart);
+        assert_eq!(frame_numbers(&input_frames), vec![1, 2, 3]);
+        assert_eq!(player.position(), 4);
+    }
+
+    #[test]
+    fn seeking_backwards_restarts_from_the_first_frame() {
+        let mut player = numbered_player();
+        for _ in 0..6 {
+            player.advance();
+        }
+
+        player.seek(2);
+        let input_frames = player.advance();
+
+        assert!(input_frames.restart);
+        assert_eq!(frame_numbers(&input_frames), vec![0, 1]);
+        assert_eq!(player.position(), 2);
+    }
+
+    #[test]
+    fn seeking_past_the_end_stops_at_the_last_frame() {
+        let mut player = numbered_player();
+
+        player.seek(1000);
+        assert_eq!(player.seek_target(), player.len());
+
+        let input_frames = player.advance();
+        assert_eq!(input_frames.frames.len(), 10);
+        assert_eq!(player.position(), player.len());
+    }
+
+    #[test]
+    fn slow_motion_carries_partial_frames_between_updates() {
+        let mut player = numbered_player();
+        player.set_speed(0.25);
+
+        let frame_counts: Vec<usize> = (0..8).map(|_| player.advance().frames.len()).collect();
+        assert_eq!(frame_counts, vec![0, 0, 0, 1, 0, 0, 0, 1]);
+        assert_eq!(player.position(), 2);
+    }
+
+    #[test]
+    fn seeking_works_while_paused() {
+        let mut player = numbered_player();
+        player.toggle_paused();
+
+        assert!(player.advance().frames.is_empty());
+
+        player.seek(3);
+        let input_frames = player.advance();
+        assert_eq!(frame_numbers(&input_frames), vec![0, 1, 2]);
+        assert!(player.is_paused());
+
+        // the player stays paused at the seek target
+        assert!(player.advance().frames.is_empty());
+        assert_eq!(player.position(), 3);
+    }
+}
@@ -0,0 +1,103 @@
+use super::playfield::TSpin;
+use super::streaks::StreakReport;
+
+// points for clearing 0, 1, 2, 3, or 4 lines with a single piece, before multiplying by the level
+const LINE_CLEAR_POINTS: [u32; 5] = [0, 100, 300, 500, 800];
+
+// points for T-spins that clear 0, 1, 2, or 3 lines, before multiplying by the level
+const MINI_T_SPIN_POINTS: [u32; 3] = [100, 200, 400];
+const T_SPIN_POINTS: [u32; 4] = [400, 800, 1200, 1600];
+
+// drop points are awarded per cell travelled, and don't depend on the level
+const SOFT_DROP_POINTS_PER_CELL: u32 = 1;
+const HARD_DROP_POINTS_PER_CELL: u32 = 2;
+
+// each step of a combo is worth this many points, multiplied by the combo count and the level
+const COMBO_POINTS: u32 = 50;
+
+// difficult clears that continue a back-to-back chain are worth this much more, as a ratio
+const BACK_TO_BACK_NUMERATOR: u32 = 3;
+const BACK_TO_BACK_DENOMINATOR: u32 = 2;
+
+const LINES_PER_LEVEL: u32 = 10;
+
+// past this level, the guideline gravity formula stops getting faster and starts misbehaving
+const MAX_GRAVITY_LEVEL: u32 = 20;
+
+// there's no point starting any higher than the level where gravity stops speeding up
+pub const MAX_STARTING_LEVEL: u32 = MAX_GRAVITY_LEVEL;
+
+pub struct Scoring {
+    score: u32,
+    lines: u32,
+    level: u32,
+}
+
+impl Scoring {
+    pub fn new(starting_level: u32) -> Self {
+        Self {
+            score: 0,
+            lines: 0,
+            level: starting_level,
+        }
+    }
+
+    // Awards points for locking a piece. T-spins are worth points even if they don't clear any lines.
+    pub fn award_line_clear(&mut self, lines_cleared: usize, t_spin: TSpin, streaks: &StreakReport) {
+        let mut clear_points = match t_spin {
+            TSpin::None => LINE_CLEAR_POINTS[lines_cleared],
+            TSpin::Mini => MINI_T_SPIN_POINTS[lines_cleared.min(MINI_T_SPIN_POINTS.len() - 1)],
+            TSpin::Full => T_SPIN_POINTS[lines_cleared.min(T_SPIN_POINTS.len() - 1)],
+        };
+        if streaks.back_to_back {
+            clear_points = clear_points * BACK_TO_BACK_NUMERATOR / BACK_TO_BACK_DENOMINATOR;
+        }
+
+        let combo_points = COMBO_POINTS * streaks.combo.unwrap_or(0);
+
+        self.score += (clear_points + combo_points) * self.level;
+
+        // level up every time we cross a multiple of LINES_PER_LEVEL
+        self.lines += lines_cleared as u32;
+        while self.lines_to_next_level() == 0 {
+            self.level += 1;
+        }
+    }
+
+    pub fn award_soft_drop(&mut self, cells: u32) {
+        self.score += cells * SOFT_DROP_POINTS_PER_CELL;
+    }
+
+    pub fn award_hard_drop(&mut self, cells: u32) {
+        self.score += cells * HARD_DROP_POINTS_PER_CELL;
+    }
+
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    pub fn lines(&self) -> u32 {
+        self.lines
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn lines_to_next_level(&self) -> u32 {
+        (self.level * LINES_PER_LEVEL).saturating_sub(self.lines)
+    }
+
+    // How long it takes gravity to move the active piece down one row at the current level
+    pub fn gravity_delay(&self) -> f32 {
+        gravity_delay(self.level)
+    }
+}
+
+// The guideline gravity curve: (0.8 - (level - 1) * 0.007) ^ (level - 1) seconds per row
+pub fn gravity_delay(level: u32) -> f32 {
+    let level = level.max(1).min(MAX_GRAVITY_LEVEL);
+    let exponent = (level - 1) as i32;
+
+    (0.8 - exponent as f32 * 0.007).powi(exponent)
+}
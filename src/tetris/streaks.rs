@@ -0,0 +1,64 @@
+use super::playfield::{LockResult, TSpin};
+
+// Tracks combos (consecutive locks that clear lines) and back-to-backs (consecutive "difficult" clears, ie tetrises and T-spins, with no easy clears in between)
+pub struct ClearStreaks {
+    // how many locks in a row have cleared lines, minus one. None when the most recent lock didn't clear anything.
+    combo: Option<u32>,
+
+    // how many difficult clears in a row we've had, minus one. None once an easy clear breaks the chain.
+    back_to_back: Option<u32>,
+}
+
+// the state of the streaks right after a single lock
+#[derive(Clone, Copy)]
+pub struct StreakReport {
+    pub combo: Option<u32>,
+
+    // true if this lock was a difficult clear that continued a chain of difficult clears
+    pub back_to_back: bool,
+}
+
+impl ClearStreaks {
+    pub fn new() -> Self {
+        Self {
+            combo: None,
+            back_to_back: None,
+        }
+    }
+
+    pub fn record_lock(&mut self, lock_result: &LockResult) -> StreakReport {
+        let lines_cleared = lock_result.lines_cleared();
+
+        // locks that don't clear any lines end the combo, but don't affect back-to-back -- otherwise you could never set up the next tetris
+        if lines_cleared == 0 {
+            self.combo = None;
+            return StreakReport { combo: None, back_to_back: false };
+        }
+
+        self.combo = Some(self.combo.map_or(0, |combo| combo + 1));
+
+        let difficult = lines_cleared >= 4 || lock_result.t_spin != TSpin::None;
+        let continues_chain = difficult && self.back_to_back.is_some();
+        self.back_to_back = if difficult {
+            Some(self.back_to_back.map_or(0, |chain| chain + 1))
+        }
+        else {
+            None
+        };
+
+        StreakReport {
+            combo: self.combo,
+            back_to_back: continues_chain,
+        }
+    }
+
+    #[allow(unused)]
+    pub fn combo(&self) -> Option<u32> {
+        self.combo
+    }
+
+    #[allow(unused)]
+    pub fn back_to_back(&self) -> Option<u32> {
+        self.back_to_back
+    }
+}
@@ -2,6 +2,21 @@
 use ::engine::BinaryAxis;
 use piston_window::{Input, Button, Key};
 
+use super::game::GameInput;
+use super::replay::{Replay, ReplayFrame};
+use super::replay_player::{ReplayPlayer, InputFrames};
+
+// replay playback controls: hold up to fast forward, hold down for slow motion, and tap left/right to seek
+const FAST_FORWARD_SPEED: f32 = 4.0;
+const SLOW_MOTION_SPEED: f32 = 0.25;
+const SEEK_STEP_FRAMES: usize = 150;
+
+// Where the game's inputs come from: either the player's keyboard, or a recorded replay. In replay mode, the keyboard controls playback instead.
+pub enum InputSource {
+    Keyboard,
+    Replay(ReplayPlayer),
+}
+
 pub struct TetrisInput {
     pub arrow_left: BinaryAxis,
     pub arrow_right: BinaryAxis,
@@ -11,10 +26,29 @@ pub struct TetrisInput {
     pub key_a: BinaryAxis,
     pub key_s: BinaryAxis,
     pub key_d: BinaryAxis,
+
+    pub key_r: BinaryAxis,
+    pub key_p: BinaryAxis,
+
+    // true if the window lost focus at some point during the most recent update
+    pub focus_lost_this_frame: bool,
+
+    source: InputSource,
 }
 
 impl TetrisInput {
     pub fn new() -> Self {
+        Self::with_source(InputSource::Keyboard)
+    }
+
+    pub fn from_replay(replay: Replay, start_frame: usize) -> Self {
+        let mut player = ReplayPlayer::new(replay);
+        player.seek(start_frame);
+
+        Self::with_source(InputSource::Replay(player))
+    }
+
+    fn with_source(source: InputSource) -> Self {
         Self {
             arrow_left: BinaryAxis::new(),
             arrow_right: BinaryAxis::new(),
@@ -24,12 +58,25 @@ impl TetrisInput {
             key_a: BinaryAxis::new(),
             key_s: BinaryAxis::new(),
             key_d: BinaryAxis::new(),
+
+            key_r: BinaryAxis::new(),
+            key_p: BinaryAxis::new(),
+
+            focus_lost_this_frame: false,
+
+            source,
         }
     }
 
     pub fn update(&mut self, dt: f32, input_events: &[Input]) {
+        self.focus_lost_this_frame = false;
+
         // loop through all of the input events that happened this frame
         for entry in input_events {
+            if let &Input::Focus(false) = entry {
+                self.focus_lost_this_frame = true;
+            }
+
             if let &Input::Button(button_input) = entry {
                 match button_input.button {
                     Button::Keyboard(Key::Left) => self.arrow_left.state_change(&button_input.state),
@@ -40,6 +87,9 @@ impl TetrisInput {
                     Button::Keyboard(Key::A) => self.key_a.state_change(&button_input.state),
                     Button::Keyboard(Key::S) => self.key_s.state_change(&button_input.state),
                     Button::Keyboard(Key::D) => self.key_d.state_change(&button_input.state),
+
+                    Button::Keyboard(Key::R) => self.key_r.state_change(&button_input.state),
+                    Button::Keyboard(Key::P) => self.key_p.state_change(&button_input.state),
                     _ => {}
                 }
             }
@@ -54,5 +104,73 @@ impl TetrisInput {
         self.key_a.update(dt);
         self.key_s.update(dt);
         self.key_d.update(dt);
+
+        self.key_r.update(dt);
+        self.key_p.update(dt);
+    }
+
+    pub fn replay_player(&self) -> Option<&ReplayPlayer> {
+        match self.source {
+            InputSource::Keyboard => None,
+            InputSource::Replay(ref player) => Some(player),
+        }
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.replay_player().is_some()
+    }
+
+    // The game frames to simulate during this update. From the keyboard, that's always exactly one frame. From a replay, it depends on the playback controls.
+    pub fn game_frames(&mut self, dt: f32) -> InputFrames {
+        let keyboard_input = self.keyboard_game_input();
+
+        match self.source {
+            InputSource::Keyboard => InputFrames {
+                restart: false,
+                frames: vec![ReplayFrame { dt, input: keyboard_input }],
+            },
+            InputSource::Replay(ref mut player) => {
+                if self.key_p.pressed_this_frame() {
+                    player.toggle_paused();
+                }
+
+                if self.key_r.pressed_this_frame() {
+                    player.seek(0);
+                }
+                if self.arrow_left.pressed_this_frame() {
+                    let target = player.seek_target().saturating_sub(SEEK_STEP_FRAMES);
+                    player.seek(target);
+                }
+                if self.arrow_right.pressed_this_frame() {
+                    let target = player.seek_target() + SEEK_STEP_FRAMES;
+                    player.seek(target);
+                }
+
+                if self.arrow_up.pressed() {
+                    player.set_speed(FAST_FORWARD_SPEED);
+                }
+                else if self.arrow_down.pressed() {
+                    player.set_speed(SLOW_MOTION_SPEED);
+                }
+                else {
+                    player.set_speed(1.0);
+                }
+
+                player.advance()
+            }
+        }
+    }
+
+    // The logical game inputs for this frame, derived from the keys that are currently held
+    fn keyboard_game_input(&self) -> GameInput {
+        GameInput {
+            left: self.arrow_left.pressed(),
+            right: self.arrow_right.pressed(),
+            soft_drop: self.arrow_down.pressed(),
+            hard_drop: self.arrow_up.pressed(),
+            rotate_left: self.key_a.pressed(),
+            rotate_right: self.key_d.pressed(),
+            hold: self.key_s.pressed(),
+        }
     }
 }
\ No newline at end of file
@@ -1,12 +1,11 @@
 
 use image::Rgb;
-use rand::Rng;
 
 use num_traits::Zero;
 
 use ::engine::intvector::{IntVector2, CardinalRotation};
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum PieceType {
     IBlock,
     OBlock,
@@ -17,6 +16,8 @@ pub enum PieceType {
     TBlock,
 }
 
+pub const ALL_PIECE_TYPES: [PieceType; 7] = [PieceType::IBlock, PieceType::LBlock, PieceType::JBlock, PieceType::SBlock, PieceType::ZBlock, PieceType::TBlock, PieceType::OBlock];
+
 impl PieceType {
     pub fn get_color(&self) -> Rgb<u8> {
         match *self {
@@ -45,9 +46,9 @@ pub struct Tetromino {
 }
 
 impl Tetromino {
-    pub fn new_random(position: IntVector2<i8>, rng: &mut impl Rng) -> Self {
+    pub fn new(piece_type: PieceType, position: IntVector2<i8>) -> Self {
         Self {
-            piece_type: *rng.choose(&[PieceType::IBlock, PieceType::LBlock, PieceType::JBlock, PieceType::SBlock, PieceType::ZBlock, PieceType::TBlock, PieceType::OBlock]).unwrap(),
+            piece_type,
             orientation: CardinalRotation::Rotate0,
             position,
         }
@@ -111,8 +112,23 @@ impl Tetromino {
     pub fn get_color(&self) -> Rgb<u8> {
         self.piece_type.get_color()
     }
+
+    pub fn piece_type(&self) -> PieceType {
+        self.piece_type
+    }
+
+    pub fn orientation(&self) -> CardinalRotation {
+        self.orientation
+    }
+
+    pub fn position(&self) -> IntVector2<i8> {
+        self.position
+    }
 }
 
+// garbage rows are grey, so they stand out from the blocks the player placed
+pub const GARBAGE_COLOR: [u8; 3] = [110,110,110];
+
 pub struct OrphanBlock {
     pub color: Rgb<u8>,
 }
@@ -0,0 +1,92 @@
+use ::engine::intvector::{IntVector2, CardinalRotation};
+
+use super::tetromino::{Tetromino, PieceType};
+use super::playfield::Playfield;
+
+// Super Rotation System kick tests, with +y pointing up. When a rotation is blocked, each offset is tried in order, and the first one that fits wins.
+// Orientations are named the SRS way: 0 is the spawn orientation, R is one clockwise turn from spawn, 2 is two turns, L is one counterclockwise turn.
+static JLSTZ_KICKS_0_R: [(i8, i8); 5] = [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)];
+static JLSTZ_KICKS_R_0: [(i8, i8); 5] = [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)];
+static JLSTZ_KICKS_2_L: [(i8, i8); 5] = [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)];
+static JLSTZ_KICKS_L_2: [(i8, i8); 5] = [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)];
+
+static I_KICKS_0_R: [(i8, i8); 5] = [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)];
+static I_KICKS_R_0: [(i8, i8); 5] = [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)];
+static I_KICKS_R_2: [(i8, i8); 5] = [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)];
+static I_KICKS_2_R: [(i8, i8); 5] = [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)];
+static I_KICKS_2_L: [(i8, i8); 5] = [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)];
+static I_KICKS_L_2: [(i8, i8); 5] = [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)];
+static I_KICKS_L_0: [(i8, i8); 5] = [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)];
+static I_KICKS_0_L: [(i8, i8); 5] = [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)];
+
+// the position of the last test in each kick table. T-spins that need this kick are always treated as full T-spins, never minis.
+pub const FINAL_KICK_INDEX: usize = 4;
+
+// the O piece doesn't visibly change when it rotates, so it never needs to kick
+static O_KICKS: [(i8, i8); 1] = [(0, 0)];
+
+pub struct KickedRotation {
+    pub tetromino: Tetromino,
+
+    // the offset that made the rotation fit, and its position in the kick table. Index 0 means the piece rotated in place.
+    #[allow(unused)]
+    pub kick_offset: IntVector2<i8>,
+    pub kick_index: usize,
+}
+
+// Maps our orientations onto SRS's names: 0 = spawn, 1 = R, 2 = 2, 3 = L
+fn srs_orientation_index(orientation: CardinalRotation) -> usize {
+    match orientation {
+        CardinalRotation::Rotate0 => 0,
+        CardinalRotation::Rotate270 => 1,
+        CardinalRotation::Rotate180 => 2,
+        CardinalRotation::Rotate90 => 3,
+    }
+}
+
+fn kick_table(piece_type: PieceType, from: CardinalRotation, to: CardinalRotation) -> &'static [(i8, i8)] {
+    let transition = (srs_orientation_index(from), srs_orientation_index(to));
+
+    match piece_type {
+        PieceType::OBlock => &O_KICKS,
+        PieceType::IBlock => match transition {
+            (0, 1) => &I_KICKS_0_R,
+            (1, 0) => &I_KICKS_R_0,
+            (1, 2) => &I_KICKS_R_2,
+            (2, 1) => &I_KICKS_2_R,
+            (2, 3) => &I_KICKS_2_L,
+            (3, 2) => &I_KICKS_L_2,
+            (3, 0) => &I_KICKS_L_0,
+            (0, 3) => &I_KICKS_0_L,
+            _ => &O_KICKS,
+        },
+        _ => match transition {
+            (0, 1) | (2, 1) => &JLSTZ_KICKS_0_R,
+            (1, 0) | (1, 2) => &JLSTZ_KICKS_R_0,
+            (2, 3) | (0, 3) => &JLSTZ_KICKS_2_L,
+            (3, 2) | (3, 0) => &JLSTZ_KICKS_L_2,
+            _ => &O_KICKS,
+        },
+    }
+}
+
+// Tries to place `rotated`, which must be a rotation of `tetromino`, on the playfield, kicking it away from obstacles if necessary.
+// Returns None if none of the kick tests fit.
+pub fn rotate_with_kicks(playfield: &Playfield, tetromino: &Tetromino, rotated: &Tetromino) -> Option<KickedRotation> {
+    let kicks = kick_table(tetromino.piece_type(), tetromino.orientation(), rotated.orientation());
+
+    for (kick_index, &(x, y)) in kicks.iter().enumerate() {
+        let kick_offset = IntVector2::new(x, y);
+        let kicked_tetromino = rotated.moved(kick_offset);
+
+        if playfield.is_valid_placement(&kicked_tetromino) {
+            return Some(KickedRotation {
+                tetromino: kicked_tetromino,
+                kick_offset,
+                kick_index,
+            });
+        }
+    }
+
+    None
+}